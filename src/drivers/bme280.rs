@@ -0,0 +1,315 @@
+//! Bosch BME280 temperature, humidity and pressure sensor over I2C
+
+use crate::error::Error;
+use crate::hal::I2cDevice;
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::Timed,
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+
+const CHIP_ID: u8 = 0x60;
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_CALIB_00: u8 = 0x88;
+const REG_CALIB_26: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_DATA: u8 = 0xF7;
+
+/// Address selected by the SDO pin
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Address {
+    Low = 0x76,
+    High = 0x77,
+}
+
+/// Factory trimming values used to compensate the raw readings
+#[derive(Clone, Default, Debug)]
+struct Calibration {
+    t1: f64,
+    t2: f64,
+    t3: f64,
+    p: [f64; 9],
+    h1: f64,
+    h2: f64,
+    h3: f64,
+    h4: f64,
+    h5: f64,
+    h6: f64,
+}
+
+impl Calibration {
+    fn parse(first: &[u8; 26], second: &[u8; 7]) -> Self {
+        let u16_at = |i: usize| u16::from_le_bytes([first[i], first[i + 1]]) as f64;
+        let i16_at = |i: usize| i16::from_le_bytes([first[i], first[i + 1]]) as f64;
+
+        let mut p = [0.0; 9];
+        p[0] = u16_at(6);
+        for (n, value) in p.iter_mut().enumerate().skip(1) {
+            *value = i16_at(6 + n * 2);
+        }
+
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p,
+            h1: first[25] as f64,
+            h2: i16::from_le_bytes([second[0], second[1]]) as f64,
+            h3: second[2] as f64,
+            h4: ((second[3] as i8 as i16) << 4 | (second[4] & 0x0F) as i16) as f64,
+            h5: ((second[5] as i8 as i16) << 4 | (second[4] >> 4) as i16) as f64,
+            h6: second[6] as i8 as f64,
+        }
+    }
+
+    /// Returns the temperature in celsius and the fine temperature used by the other values
+    fn temperature(&self, adc: f64) -> (f64, f64) {
+        let var1 = (adc / 16384.0 - self.t1 / 1024.0) * self.t2;
+        let var2 = (adc / 131072.0 - self.t1 / 8192.0).powi(2) * self.t3;
+        let fine = var1 + var2;
+        (fine / 5120.0, fine)
+    }
+
+    /// Pressure in pascal
+    fn pressure(&self, adc: f64, fine: f64) -> f64 {
+        let p = &self.p;
+        let mut var1 = fine / 2.0 - 64000.0;
+        let mut var2 = var1 * var1 * p[5] / 32768.0;
+        var2 += var1 * p[4] * 2.0;
+        var2 = var2 / 4.0 + p[3] * 65536.0;
+        var1 = (p[2] * var1 * var1 / 524288.0 + p[1] * var1) / 524288.0;
+        var1 = (1.0 + var1 / 32768.0) * p[0];
+        if var1 == 0.0 {
+            return 0.0;
+        }
+
+        let mut pressure = 1048576.0 - adc;
+        pressure = (pressure - var2 / 4096.0) * 6250.0 / var1;
+        var1 = p[8] * pressure * pressure / 2147483648.0;
+        var2 = pressure * p[7] / 32768.0;
+        pressure + (var1 + var2 + p[6]) / 16.0
+    }
+
+    /// Relative humidity
+    fn humidity(&self, adc: f64, fine: f64) -> f64 {
+        let mut h = fine - 76800.0;
+        h = (adc - (self.h4 * 64.0 + self.h5 / 16384.0 * h))
+            * (self.h2 / 65536.0
+                * (1.0 + self.h6 / 67108864.0 * h * (1.0 + self.h3 / 67108864.0 * h)));
+        h *= 1.0 - self.h1 * h / 524288.0;
+        h.clamp(0.0, 100.0)
+    }
+}
+
+/// 20 bit value stored as msb, lsb and the xlsb's top nibble
+fn raw20(data: &[u8]) -> u32 {
+    (data[0] as u32) << 12 | (data[1] as u32) << 4 | (data[2] as u32) >> 4
+}
+
+/// Module that sets up a single BME280 on an I2C bus
+pub struct Bme280 {
+    name: String,
+    bus: String,
+    address: Address,
+}
+
+impl Bme280 {
+    pub fn new(name: &str, bus: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+            address: Address::Low,
+        }
+    }
+
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
+    }
+}
+
+impl Module<SensorHandle<Bme280Sensor>> for Bme280 {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Bme280Sensor> {
+        let device = robot
+            .i2c_device(&self.bus, self.address as u8)
+            .unwrap_or_else(|| panic!("BME280 needs the {} I2C bus", self.bus));
+        let sensor = Bme280Sensor::new(device);
+
+        SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_device(&sensor.device)
+            .with_output(OutputType::TEMPERATURE)
+            .with_output(OutputType::HUMIDITY)
+            .with_output(OutputType::PRESSURE)
+            .with_system(bme280_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Bme280Sensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Bme280Sensor {
+    device: I2cDevice,
+    // Loaded on the first read
+    calibration: Option<Calibration>,
+}
+
+impl Bme280Sensor {
+    pub fn new(device: I2cDevice) -> Self {
+        Self {
+            device,
+            calibration: None,
+        }
+    }
+
+    /// Checks the chip id, loads the calibration and starts normal mode
+    /// with 1x oversampling on every measurement
+    fn configure(&mut self) -> Result<Calibration, Error> {
+        let mut id = [0];
+        self.device.write_read(&[REG_CHIP_ID], &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(Error::Disconnected);
+        }
+
+        let mut first = [0; 26];
+        let mut second = [0; 7];
+        self.device.write_read(&[REG_CALIB_00], &mut first)?;
+        self.device.write_read(&[REG_CALIB_26], &mut second)?;
+
+        self.device.write(&[REG_CTRL_HUM, 0x01])?;
+        self.device.write(&[REG_CTRL_MEAS, 0x27])?;
+
+        Ok(Calibration::parse(&first, &second))
+    }
+
+    /// Returns the temperature in celsius, relative humidity and pressure in pascal
+    pub fn read(&mut self) -> Result<(f64, f64, f64), Error> {
+        if self.calibration.is_none() {
+            self.calibration = Some(self.configure()?);
+        }
+
+        let mut data = [0; 8];
+        self.device.write_read(&[REG_DATA], &mut data)?;
+
+        let calibration = self.calibration.as_ref().unwrap();
+        let adc_p = raw20(&data[0..3]) as f64;
+        let adc_t = raw20(&data[3..6]) as f64;
+        let adc_h = u16::from_be_bytes([data[6], data[7]]) as f64;
+
+        let (temperature, fine) = calibration.temperature(adc_t);
+        Ok((
+            temperature,
+            calibration.humidity(adc_h, fine),
+            calibration.pressure(adc_p, fine),
+        ))
+    }
+}
+
+impl Descriptor for Bme280Sensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "bme280")
+    }
+
+    fn name(&self) -> String {
+        "BME280".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Bosch temperature, humidity and pressure sensor".to_string()
+    }
+}
+
+fn bme280_reading(
+    mut query: Query<(Entity, &mut Bme280Sensor, &Features), Timed>,
+    mut reading: Query<(&mut Reading, &OutputType)>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let result = sensor.read();
+        match result {
+            Ok(_) => faults.clear(entity),
+            Err(error) => faults.report(entity, error),
+        }
+
+        for feature in features.iter() {
+            let Ok((mut read, kind)) = reading.get_mut(*feature) else {
+                continue;
+            };
+
+            match result {
+                Ok((t, _, _)) if *kind == OutputType::TEMPERATURE => {
+                    read.set_as(t, Unit::Celsius);
+                }
+                Ok((_, rh, _)) if *kind == OutputType::HUMIDITY => {
+                    read.set_as(rh, Unit::RelativeHumidity);
+                }
+                Ok((_, _, p)) if *kind == OutputType::PRESSURE => {
+                    read.set_as(p, Unit::Pascal);
+                }
+                Ok(_) => {}
+                Err(error) => read.fail(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{mock::MockI2c, I2cHandle};
+
+    // Trimming values from the datasheet's example plus some humidity ones
+    const CALIB_00: [u8; 26] = [
+        112, 107, 67, 103, 24, 252, 125, 142, 67, 214, 208, 11, 39, 11, 140, 0, 249, 255, 140, 60,
+        248, 198, 112, 23, 0, 75,
+    ];
+    const CALIB_26: [u8; 7] = [106, 1, 0, 19, 41, 3, 30];
+    const DATA: [u8; 8] = [0x65, 0x5A, 0xC0, 0x7E, 0xED, 0x00, 0x75, 0x30];
+
+    #[test]
+    fn compensates_readings() {
+        let mock = MockI2c::new();
+        mock.expect_write_read(0x76, &[0xD0], &[0x60])
+            .expect_write_read(0x76, &[0x88], &CALIB_00)
+            .expect_write_read(0x76, &[0xE1], &CALIB_26)
+            .expect_write(0x76, &[0xF2, 0x01])
+            .expect_write(0x76, &[0xF4, 0x27])
+            .expect_write_read(0x76, &[0xF7], &DATA)
+            .expect_write_read(0x76, &[0xF7], &DATA);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Bme280::new("BME280", "i2c-1"));
+        robot.run();
+        robot.run();
+
+        let value = |i: usize| robot.output_reading(&sensor.outputs()[i]).unwrap().value();
+        assert!((value(0) - 25.082).abs() < 0.01);
+        assert!((value(1) - 55.0).abs() < 0.01);
+        assert!((value(2) - 100_653.27).abs() < 0.1);
+        mock.done();
+    }
+
+    #[test]
+    fn rejects_other_chips() {
+        let mock = MockI2c::new();
+        mock.expect_write_read(0x77, &[0xD0], &[0x58]);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        robot.add(Bme280::new("BME280", "i2c-1").with_address(Address::High));
+        robot.run();
+
+        assert_eq!(robot.faults()[0].1.error, Error::Disconnected);
+        mock.done();
+    }
+}
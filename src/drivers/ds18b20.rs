@@ -0,0 +1,271 @@
+//! Maxim DS18B20 temperature probe, alone on a 1-Wire bus
+
+use crate::error::Error;
+use crate::hal::{OneWire, OneWireHandle};
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::{Duration, Timed},
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+
+const SKIP_ROM: u8 = 0xCC;
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xBE;
+/// Longest conversion, at 12 bits of resolution
+const CONVERSION_TIME: Duration = Duration::Millis(750);
+
+/// Dallas/Maxim CRC-8, reflected polynomial 0x8C and initial value 0
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x01 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Checks the scratchpad's CRC and returns the temperature in celsius
+fn parse(scratchpad: &[u8; 9]) -> Result<f64, Error> {
+    if crc8(&scratchpad[0..8]) != scratchpad[8] {
+        return Err(Error::CrcMismatch);
+    }
+    Ok(i16::from_le_bytes([scratchpad[0], scratchpad[1]]) as f64 / 16.0)
+}
+
+/// Module that sets up a DS18B20, the probe must be the only device on its bus
+pub struct Ds18b20 {
+    name: String,
+    bus: String,
+}
+
+impl Ds18b20 {
+    pub fn new(name: &str, bus: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+        }
+    }
+}
+
+impl Module<SensorHandle<Ds18b20Sensor>> for Ds18b20 {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Ds18b20Sensor> {
+        let bus = robot
+            .bus::<OneWireHandle>(&self.bus)
+            .unwrap_or_else(|| panic!("DS18B20 needs the {} 1-Wire bus", self.bus));
+        let sensor = Ds18b20Sensor::new(bus);
+
+        SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_output(OutputType::TEMPERATURE)
+            .with_timer(Some(CONVERSION_TIME))
+            .with_system(ds18b20_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Ds18b20Sensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Ds18b20Sensor {
+    bus: OneWireHandle,
+    // A conversion was started by the previous read
+    converting: bool,
+}
+
+impl Ds18b20Sensor {
+    pub fn new(bus: OneWireHandle) -> Self {
+        Self {
+            bus,
+            converting: false,
+        }
+    }
+
+    fn command(bus: &mut dyn OneWire, command: u8) -> Result<(), Error> {
+        if !bus.reset()? {
+            return Err(Error::Disconnected);
+        }
+        bus.write_byte(SKIP_ROM)?;
+        bus.write_byte(command)
+    }
+
+    /// A conversion takes up to 750ms, so instead of blocking every read fetches
+    /// the result of the conversion started by the previous one and starts the next.
+    /// Reads must be at least that far apart, the module's timer takes care of it.
+    /// Returns None on the first read since nothing has been converted yet
+    pub fn read(&mut self) -> Result<Option<f64>, Error> {
+        let converting = std::mem::take(&mut self.converting);
+        let temperature = self.bus.exclusive(|bus| {
+            let temperature = if converting {
+                Self::command(bus, READ_SCRATCHPAD)?;
+                let mut scratchpad = [0; 9];
+                for byte in scratchpad.iter_mut() {
+                    *byte = bus.read_byte()?;
+                }
+                Some(parse(&scratchpad)?)
+            } else {
+                None
+            };
+
+            Self::command(bus, CONVERT_T)?;
+            Ok(temperature)
+        })?;
+
+        self.converting = true;
+        Ok(temperature)
+    }
+}
+
+impl Descriptor for Ds18b20Sensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "ds18b20")
+    }
+
+    fn name(&self) -> String {
+        "DS18B20".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Maxim 1-Wire temperature probe".to_string()
+    }
+}
+
+fn ds18b20_reading(
+    mut query: Query<(Entity, &mut Ds18b20Sensor, &Features), Timed>,
+    mut reading: Query<&mut Reading>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let Ok(mut read) = reading.get_mut(features[0]) else {
+            continue;
+        };
+
+        match sensor.read() {
+            Ok(Some(t)) => {
+                read.set_as(t, Unit::Celsius);
+                faults.clear(entity);
+            }
+            Ok(None) => faults.clear(entity),
+            Err(error) => {
+                read.fail(error);
+                faults.report(entity, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mock::MockOneWire;
+    use crate::modules::clock::MockClock;
+    use crate::prelude::*;
+
+    // Scratchpad holding 25.0625°C, from the datasheet's temperature table
+    const SCRATCHPAD: [u8; 9] = [0x91, 0x01, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0x70];
+
+    #[test]
+    fn crc_matches_app_note() {
+        assert_eq!(crc8(&[0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00]), 0xA2);
+        assert_eq!(parse(&SCRATCHPAD), Ok(25.0625));
+
+        let mut negative = SCRATCHPAD;
+        negative[0] = 0x5E;
+        negative[1] = 0xFF;
+        negative[8] = 0x6A;
+        assert_eq!(parse(&negative), Ok(-10.125));
+    }
+
+    #[test]
+    fn reads_the_previous_conversion() {
+        let mock = MockOneWire::new();
+        mock.expect_reset(true)
+            .expect_write(&[0xCC, 0x44])
+            .expect_reset(true)
+            .expect_write(&[0xCC, 0xBE])
+            .expect_read(&SCRATCHPAD)
+            .expect_reset(true)
+            .expect_write(&[0xCC, 0x44]);
+
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_bus("w1", OneWireHandle::new(mock.clone()));
+        let sensor = robot.add(Ds18b20::new("Probe", "w1"));
+        let feature = sensor.output(OutputType::TEMPERATURE).unwrap();
+        let status = |robot: &Robot| robot.output_reading(&feature).unwrap().status();
+
+        // Nothing is read before the first conversion time has passed
+        robot.run();
+        clock.advance(std::time::Duration::from_millis(750));
+        robot.run();
+        assert_eq!(status(&robot), Status::Uninitialised);
+
+        clock.advance(std::time::Duration::from_millis(750));
+        robot.run();
+        let reading = robot.output_reading(&feature).unwrap();
+        assert_eq!(reading.value(), 25.0625);
+        mock.done();
+    }
+
+    #[test]
+    fn converts_once_per_conversion_time() {
+        let mock = MockOneWire::new();
+        mock.expect_reset(true).expect_write(&[0xCC, 0x44]);
+        for _ in 0..2 {
+            mock.expect_reset(true)
+                .expect_write(&[0xCC, 0xBE])
+                .expect_read(&SCRATCHPAD)
+                .expect_reset(true)
+                .expect_write(&[0xCC, 0x44]);
+        }
+
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_bus("w1", OneWireHandle::new(mock.clone()));
+        let sensor = robot.add(Ds18b20::new("Probe", "w1"));
+        let feature = sensor.output(OutputType::TEMPERATURE).unwrap();
+
+        // Ticks every 250ms, the bus only sees a cycle on every third one
+        for _ in 0..10 {
+            robot.run();
+            clock.advance(std::time::Duration::from_millis(250));
+        }
+
+        assert_eq!(robot.output_reading(&feature).unwrap().sequence(), 2);
+        mock.done();
+    }
+
+    #[test]
+    fn missing_probe_faults() {
+        let mock = MockOneWire::new();
+        mock.expect_reset(false);
+
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_bus("w1", OneWireHandle::new(mock.clone()));
+        robot.add(Ds18b20::new("Probe", "w1"));
+        robot.run();
+        clock.advance(std::time::Duration::from_millis(750));
+        robot.run();
+
+        assert_eq!(robot.faults()[0].1.error, Error::Disconnected);
+        mock.done();
+    }
+}
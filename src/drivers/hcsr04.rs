@@ -0,0 +1,221 @@
+//! HC-SR04 ultrasonic distance sensor on two GPIO pins
+
+use crate::error::Error;
+use crate::hal::{InputPinHandle, OutputPinHandle};
+use crate::modules::{
+    clock::{Clock, RealClock},
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::Timed,
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use embedded_hal::digital::{InputPin, OutputPin};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Speed of sound in dry air at 20°C, in m/s
+const SPEED_OF_SOUND: f64 = 343.0;
+const TRIGGER_PULSE: Duration = Duration::from_micros(10);
+/// How long the echo may take to start after the trigger
+const ECHO_START_TIMEOUT: Duration = Duration::from_millis(10);
+/// The sensor holds the echo for 38ms when nothing reflected the burst
+const MAX_ECHO: Duration = Duration::from_millis(38);
+
+/// Module that sets up a HC-SR04 using the named trigger and echo pins
+pub struct Hcsr04 {
+    name: String,
+    trigger: String,
+    echo: String,
+    clock: Arc<dyn Clock>,
+}
+
+impl Hcsr04 {
+    pub fn new(name: &str, trigger: &str, echo: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            trigger: trigger.to_string(),
+            echo: echo.to_string(),
+            clock: Arc::new(RealClock::new()),
+        }
+    }
+
+    /// Clock used to time the echo pulse
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl Module<SensorHandle<Hcsr04Sensor>> for Hcsr04 {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Hcsr04Sensor> {
+        let trigger = robot
+            .bus::<OutputPinHandle>(&self.trigger)
+            .unwrap_or_else(|| panic!("HC-SR04 needs the {} output pin", self.trigger));
+        let echo = robot
+            .bus::<InputPinHandle>(&self.echo)
+            .unwrap_or_else(|| panic!("HC-SR04 needs the {} input pin", self.echo));
+        let sensor = Hcsr04Sensor::new(trigger, echo, self.clock);
+
+        SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_output(OutputType::DISTANCE)
+            .with_system(hcsr04_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Hcsr04Sensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Hcsr04Sensor {
+    trigger: OutputPinHandle,
+    echo: InputPinHandle,
+    clock: Arc<dyn Clock>,
+}
+
+impl Hcsr04Sensor {
+    pub fn new(trigger: OutputPinHandle, echo: InputPinHandle, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            trigger,
+            echo,
+            clock,
+        }
+    }
+
+    /// Busy waits until the echo pin reaches the level and returns when it did
+    fn wait_for(&mut self, high: bool, timeout: Duration, error: Error) -> Result<Duration, Error> {
+        let start = self.clock.now();
+        loop {
+            if self.echo.is_high()? == high {
+                return Ok(self.clock.now());
+            }
+            if self.clock.now().saturating_sub(start) > timeout {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Returns the distance to the nearest obstacle in meters
+    pub fn read(&mut self) -> Result<f64, Error> {
+        self.trigger.set_high()?;
+        std::thread::sleep(TRIGGER_PULSE);
+        self.trigger.set_low()?;
+
+        let start = self.wait_for(true, ECHO_START_TIMEOUT, Error::Timeout)?;
+        let end = self.wait_for(false, MAX_ECHO, Error::OutOfRange)?;
+
+        // The sound travels to the obstacle and back
+        Ok((end - start).as_secs_f64() * SPEED_OF_SOUND / 2.0)
+    }
+}
+
+impl Descriptor for Hcsr04Sensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "hcsr04")
+    }
+
+    fn name(&self) -> String {
+        "HC-SR04".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Ultrasonic distance sensor".to_string()
+    }
+}
+
+fn hcsr04_reading(
+    mut query: Query<(Entity, &mut Hcsr04Sensor, &Features), Timed>,
+    mut reading: Query<&mut Reading>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let Ok(mut read) = reading.get_mut(features[0]) else {
+            continue;
+        };
+
+        match sensor.read() {
+            Ok(distance) => {
+                read.set_as(distance, Unit::Meter);
+                faults.clear(entity);
+            }
+            Err(error) => {
+                read.fail(error);
+                faults.report(entity, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mock::MockPin;
+    use crate::modules::clock::MockClock;
+    use embedded_hal::digital;
+    use std::collections::VecDeque;
+
+    /// Echo pin that plays back levels, advancing the clock by a millisecond per poll
+    struct EchoPin {
+        clock: MockClock,
+        levels: VecDeque<bool>,
+    }
+
+    impl digital::ErrorType for EchoPin {
+        type Error = Error;
+    }
+
+    impl digital::InputPin for EchoPin {
+        fn is_high(&mut self) -> Result<bool, Error> {
+            self.clock.advance(Duration::from_millis(1));
+            Ok(self.levels.pop_front().unwrap_or(false))
+        }
+
+        fn is_low(&mut self) -> Result<bool, Error> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    fn setup(levels: &[bool]) -> (Robot, SensorHandle<Hcsr04Sensor>) {
+        let clock = MockClock::new();
+        let echo = EchoPin {
+            clock: clock.clone(),
+            levels: levels.iter().copied().collect(),
+        };
+
+        let mut robot = Robot::new()
+            .with_bus("trigger", OutputPinHandle::new(MockPin::new(false)))
+            .with_bus("echo", InputPinHandle::new(echo));
+        let sensor = robot.add(Hcsr04::new("Front", "trigger", "echo").with_clock(clock));
+        (robot, sensor)
+    }
+
+    #[test]
+    fn times_the_echo() {
+        // Rises on the 2nd poll and falls on the 8th, a 6ms pulse
+        let (mut robot, sensor) = setup(&[false, true, true, true, true, true, true, false]);
+        robot.run();
+
+        let distance = sensor.output(OutputType::DISTANCE).unwrap();
+        let reading = robot.output_reading(&distance).unwrap();
+        assert!((reading.value() - 1.029).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missing_echo_times_out() {
+        let (mut robot, _) = setup(&[]);
+        robot.run();
+        assert_eq!(robot.faults()[0].1.error, Error::Timeout);
+
+        let (mut robot, _) = setup(&[true; 50]);
+        robot.run();
+        assert_eq!(robot.faults()[0].1.error, Error::OutOfRange);
+    }
+}
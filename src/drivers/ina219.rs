@@ -0,0 +1,224 @@
+//! Texas Instruments INA219 high side current and power monitor over I2C
+
+use crate::error::Error;
+use crate::hal::I2cDevice;
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::Timed,
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+
+const REG_CONFIG: u8 = 0x00;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+/// 32V bus range, ±320mV shunt range, 12 bit samples, continuous conversions
+const CONFIG: u16 = 0x399F;
+const SHUNT_LSB: f64 = 0.000_01;
+const BUS_LSB: f64 = 0.004;
+/// Math overflow flag, the current and power are out of range
+const BUS_OVERFLOW: u16 = 0x0001;
+
+/// Module that sets up a single INA219 on an I2C bus, the outputs are
+/// the bus voltage, the current through the shunt and the power drawn
+pub struct Ina219 {
+    name: String,
+    bus: String,
+    address: u8,
+    shunt: f64,
+}
+
+impl Ina219 {
+    pub fn new(name: &str, bus: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+            address: 0x40,
+            shunt: 0.1,
+        }
+    }
+
+    /// Any address from 0x40 to 0x4F, as selected by the A0 and A1 pins
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Shunt resistance in ohms
+    pub fn with_shunt(mut self, ohms: f64) -> Self {
+        self.shunt = ohms;
+        self
+    }
+}
+
+impl Module<SensorHandle<Ina219Sensor>> for Ina219 {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Ina219Sensor> {
+        let device = robot
+            .i2c_device(&self.bus, self.address)
+            .unwrap_or_else(|| panic!("INA219 needs the {} I2C bus", self.bus));
+        let sensor = Ina219Sensor::new(device, self.shunt);
+
+        SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_device(&sensor.device)
+            .with_output(OutputType::VOLTAGE)
+            .with_output(OutputType::CURRENT)
+            .with_output(OutputType::POWER)
+            .with_system(ina219_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Ina219Sensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Ina219Sensor {
+    device: I2cDevice,
+    shunt: f64,
+    configured: bool,
+}
+
+impl Ina219Sensor {
+    pub fn new(device: I2cDevice, shunt: f64) -> Self {
+        Self {
+            device,
+            shunt,
+            configured: false,
+        }
+    }
+
+    fn register(&mut self, register: u8) -> Result<u16, Error> {
+        let mut data = [0; 2];
+        self.device.write_read(&[register], &mut data)?;
+        Ok(u16::from_be_bytes(data))
+    }
+
+    /// Returns the bus voltage in volts, the current in amperes and the power in watts
+    pub fn read(&mut self) -> Result<(f64, f64, f64), Error> {
+        if !self.configured {
+            let [high, low] = CONFIG.to_be_bytes();
+            self.device.write(&[REG_CONFIG, high, low])?;
+            self.configured = true;
+        }
+
+        let shunt = self.register(REG_SHUNT_VOLTAGE)? as i16 as f64 * SHUNT_LSB;
+        let bus = self.register(REG_BUS_VOLTAGE)?;
+        if bus & BUS_OVERFLOW != 0 {
+            return Err(Error::OutOfRange);
+        }
+
+        let voltage = (bus >> 3) as f64 * BUS_LSB;
+        let current = shunt / self.shunt;
+        Ok((voltage, current, voltage * current))
+    }
+}
+
+impl Descriptor for Ina219Sensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "ina219")
+    }
+
+    fn name(&self) -> String {
+        "INA219".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Texas Instruments current and power monitor".to_string()
+    }
+}
+
+fn ina219_reading(
+    mut query: Query<(Entity, &mut Ina219Sensor, &Features), Timed>,
+    mut reading: Query<(&mut Reading, &OutputType)>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let result = sensor.read();
+        match result {
+            Ok(_) => faults.clear(entity),
+            Err(error) => faults.report(entity, error),
+        }
+
+        for feature in features.iter() {
+            let Ok((mut read, kind)) = reading.get_mut(*feature) else {
+                continue;
+            };
+
+            match result {
+                Ok((v, _, _)) if *kind == OutputType::VOLTAGE => {
+                    read.set_as(v, Unit::Volt);
+                }
+                Ok((_, i, _)) if *kind == OutputType::CURRENT => {
+                    read.set_as(i, Unit::Ampere);
+                }
+                Ok((_, _, p)) if *kind == OutputType::POWER => {
+                    read.set_as(p, Unit::Watt);
+                }
+                Ok(_) => {}
+                Err(error) => read.fail(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{mock::MockI2c, I2cHandle};
+
+    fn read_values(robot: &Robot, sensor: &SensorHandle<Ina219Sensor>) -> Vec<f64> {
+        sensor
+            .outputs()
+            .iter()
+            .map(|o| robot.output_reading(o).unwrap().value())
+            .collect()
+    }
+
+    #[test]
+    fn measures_voltage_current_and_power() {
+        let mock = MockI2c::new();
+        // 10mV over the shunt and 12V on the bus with the conversion ready bit set
+        mock.expect_write(0x41, &[0x00, 0x39, 0x9F])
+            .expect_write_read(0x41, &[0x01], &1000i16.to_be_bytes())
+            .expect_write_read(0x41, &[0x02], &[0x5D, 0xC2])
+            .expect_write_read(0x41, &[0x01], &(-500i16).to_be_bytes())
+            .expect_write_read(0x41, &[0x02], &[0x5D, 0xC2]);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Ina219::new("Battery", "i2c-1").with_address(0x41));
+
+        robot.run();
+        let values = read_values(&robot, &sensor);
+        assert!((values[0] - 12.0).abs() < 1e-9);
+        assert!((values[1] - 0.1).abs() < 1e-9);
+        assert!((values[2] - 1.2).abs() < 1e-9);
+
+        robot.run();
+        let values = read_values(&robot, &sensor);
+        assert!((values[1] + 0.05).abs() < 1e-9);
+        mock.done();
+    }
+
+    #[test]
+    fn overflow_is_out_of_range() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x40, &[0x00, 0x39, 0x9F])
+            .expect_write_read(0x40, &[0x01], &[0x7F, 0xFF])
+            .expect_write_read(0x40, &[0x02], &[0x5D, 0xC3]);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        robot.add(Ina219::new("Battery", "i2c-1").with_shunt(0.01));
+        robot.run();
+
+        assert_eq!(robot.faults()[0].1.error, Error::OutOfRange);
+        mock.done();
+    }
+}
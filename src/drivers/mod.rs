@@ -0,0 +1,17 @@
+//! Ready to use modules for common parts
+//! Each driver is behind a cargo feature of the same name
+
+#[cfg(feature = "bme280")]
+pub mod bme280;
+#[cfg(feature = "ds18b20")]
+pub mod ds18b20;
+#[cfg(feature = "hcsr04")]
+pub mod hcsr04;
+#[cfg(feature = "ina219")]
+pub mod ina219;
+#[cfg(feature = "mpu6050")]
+pub mod mpu6050;
+#[cfg(feature = "sht3x")]
+pub mod sht3x;
+#[cfg(feature = "soil_moisture")]
+pub mod soil_moisture;
@@ -0,0 +1,280 @@
+//! InvenSense MPU6050 accelerometer and gyroscope over I2C
+
+use crate::error::Error;
+use crate::hal::I2cDevice;
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputKind, OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::Timed,
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+
+const REG_PWR_MGMT_1: u8 = 0x6B;
+const REG_ACCEL_XOUT_H: u8 = 0x3B;
+/// Sensitivity at the default ±2g range
+const ACCEL_LSB_PER_G: f64 = 16384.0;
+/// Sensitivity at the default ±250°/s range
+const GYRO_LSB_PER_DPS: f64 = 131.0;
+
+/// Address selected by the AD0 pin
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Address {
+    Low = 0x68,
+    High = 0x69,
+}
+
+/// Ids of the per axis output types, registered the first time the module is added
+pub const ACCEL_X: Id = Id::new("robotrs", "mpu6050", "accel_x");
+pub const ACCEL_Y: Id = Id::new("robotrs", "mpu6050", "accel_y");
+pub const ACCEL_Z: Id = Id::new("robotrs", "mpu6050", "accel_z");
+pub const GYRO_X: Id = Id::new("robotrs", "mpu6050", "gyro_x");
+pub const GYRO_Y: Id = Id::new("robotrs", "mpu6050", "gyro_y");
+pub const GYRO_Z: Id = Id::new("robotrs", "mpu6050", "gyro_z");
+
+/// One output type per axis so each can be looked up on its own
+fn axis_kinds() -> [OutputKind; 6] {
+    let accel = |id, name, axis| {
+        let description = format!("Linear acceleration along the {} axis", axis);
+        OutputKind::new(id, name, &description, Unit::MetersPerSecondSquared)
+    };
+    let gyro = |id, name, axis| {
+        let description = format!("Rate of rotation around the {} axis", axis);
+        OutputKind::new(id, name, &description, Unit::DegreesPerSecond)
+    };
+    [
+        accel(ACCEL_X, "Acceleration X", "X"),
+        accel(ACCEL_Y, "Acceleration Y", "Y"),
+        accel(ACCEL_Z, "Acceleration Z", "Z"),
+        gyro(GYRO_X, "Angular Velocity X", "X"),
+        gyro(GYRO_Y, "Angular Velocity Y", "Y"),
+        gyro(GYRO_Z, "Angular Velocity Z", "Z"),
+    ]
+}
+
+/// Units of the outputs in the order they're registered
+const UNITS: [Unit; 7] = [
+    Unit::StandardGravity,
+    Unit::StandardGravity,
+    Unit::StandardGravity,
+    Unit::DegreesPerSecond,
+    Unit::DegreesPerSecond,
+    Unit::DegreesPerSecond,
+    Unit::Celsius,
+];
+
+/// Module that sets up a single MPU6050 on an I2C bus, the outputs are
+/// acceleration on X, Y and Z, angular velocity on X, Y and Z then the die temperature.
+/// Each axis has its own output type, see ACCEL_X to GYRO_Z
+pub struct Mpu6050 {
+    name: String,
+    bus: String,
+    address: Address,
+}
+
+impl Mpu6050 {
+    pub fn new(name: &str, bus: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+            address: Address::Low,
+        }
+    }
+
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
+    }
+}
+
+impl Module<SensorHandle<Mpu6050Sensor>> for Mpu6050 {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Mpu6050Sensor> {
+        let device = robot
+            .i2c_device(&self.bus, self.address as u8)
+            .unwrap_or_else(|| panic!("MPU6050 needs the {} I2C bus", self.bus));
+        let sensor = Mpu6050Sensor::new(device);
+        let axes = axis_kinds().map(|kind| {
+            robot
+                .register_output_type(kind)
+                .unwrap_or_else(|e| panic!("MPU6050 output types can't be registered: {}", e))
+        });
+
+        let mut builder = SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_device(&sensor.device);
+        for axis in axes {
+            builder.set_output(axis);
+        }
+        builder
+            .with_output(OutputType::TEMPERATURE)
+            .with_system(mpu6050_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Mpu6050Sensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Mpu6050Sensor {
+    device: I2cDevice,
+    awake: bool,
+}
+
+impl Mpu6050Sensor {
+    pub fn new(device: I2cDevice) -> Self {
+        Self {
+            device,
+            awake: false,
+        }
+    }
+
+    /// Returns acceleration in g, angular velocity in °/s and temperature in celsius,
+    /// in the same order as the outputs
+    pub fn read(&mut self) -> Result<[f64; 7], Error> {
+        // The chip powers up asleep
+        if !self.awake {
+            self.device.write(&[REG_PWR_MGMT_1, 0x00])?;
+            self.awake = true;
+        }
+
+        let mut data = [0; 14];
+        self.device.write_read(&[REG_ACCEL_XOUT_H], &mut data)?;
+        let raw = |i: usize| i16::from_be_bytes([data[i * 2], data[i * 2 + 1]]) as f64;
+
+        Ok([
+            raw(0) / ACCEL_LSB_PER_G,
+            raw(1) / ACCEL_LSB_PER_G,
+            raw(2) / ACCEL_LSB_PER_G,
+            raw(4) / GYRO_LSB_PER_DPS,
+            raw(5) / GYRO_LSB_PER_DPS,
+            raw(6) / GYRO_LSB_PER_DPS,
+            raw(3) / 340.0 + 36.53,
+        ])
+    }
+}
+
+impl Descriptor for Mpu6050Sensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "mpu6050")
+    }
+
+    fn name(&self) -> String {
+        "MPU6050".to_string()
+    }
+
+    fn description(&self) -> String {
+        "InvenSense 6 axis accelerometer and gyroscope".to_string()
+    }
+}
+
+fn mpu6050_reading(
+    mut query: Query<(Entity, &mut Mpu6050Sensor, &Features), Timed>,
+    mut reading: Query<&mut Reading>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let result = sensor.read();
+        match result {
+            Ok(_) => faults.clear(entity),
+            Err(error) => faults.report(entity, error),
+        }
+
+        for (i, feature) in features.iter().enumerate().take(UNITS.len()) {
+            let Ok(mut read) = reading.get_mut(*feature) else {
+                continue;
+            };
+
+            match result {
+                Ok(values) => {
+                    read.set_as(values[i], UNITS[i]);
+                }
+                Err(error) => read.fail(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{mock::MockI2c, I2cHandle};
+    use crate::prelude::*;
+
+    #[test]
+    fn wakes_and_scales_readings() {
+        let mut data = vec![];
+        // Flat on a table, 1g on Z, turning at 10°/s around Z, 340 LSB over 36.53°C
+        for raw in [0i16, -8192, 16384, 340, 131, 0, 1310] {
+            data.extend(raw.to_be_bytes());
+        }
+
+        let mock = MockI2c::new();
+        mock.expect_write(0x69, &[0x6B, 0x00])
+            .expect_write_read(0x69, &[0x3B], &data)
+            .expect_write_read(0x69, &[0x3B], &data);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Mpu6050::new("IMU", "i2c-1").with_address(Address::High));
+        robot.run();
+        robot.run();
+
+        // Acceleration is stored in the type's m/s²
+        let reading = robot.output_reading(&sensor.outputs()[2]).unwrap();
+        assert_eq!(reading.unit(), Unit::MetersPerSecondSquared);
+        assert!((reading.value() - 9.806_65).abs() < 1e-9);
+
+        let expected = [0.0, -0.5, 1.0, 1.0, 0.0, 10.0, 37.53];
+        for (output, (expected, unit)) in sensor.outputs().iter().zip(expected.iter().zip(UNITS)) {
+            let reading = robot.output_reading(output).unwrap();
+            assert!((reading.get_as(unit).unwrap() - expected).abs() < 1e-9);
+        }
+        mock.done();
+    }
+
+    #[test]
+    fn axes_have_their_own_types() {
+        let mock = MockI2c::new();
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let first = robot.add(Mpu6050::new("Body", "i2c-1"));
+        let second = robot.add(Mpu6050::new("Arm", "i2c-1").with_address(Address::High));
+
+        let ids = [ACCEL_X, ACCEL_Y, ACCEL_Z, GYRO_X, GYRO_Y, GYRO_Z];
+        for (i, id) in ids.iter().enumerate() {
+            let kind = robot.output_types().find_id(id).unwrap();
+            assert_eq!(first.output(kind), Some(first.outputs()[i]));
+            assert_eq!(second.output(kind), Some(second.outputs()[i]));
+            assert_eq!(first.outputs_of(kind).count(), 1);
+        }
+        assert_eq!(
+            robot
+                .output_types()
+                .get(first.outputs()[5].kind())
+                .unwrap()
+                .unit,
+            Unit::DegreesPerSecond
+        );
+    }
+
+    #[test]
+    fn bus_errors_fault_every_output() {
+        let mock = MockI2c::new();
+        mock.expect_error(0x68, Error::Bus);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Mpu6050::new("IMU", "i2c-1"));
+        robot.run();
+
+        for output in sensor.outputs() {
+            let reading = robot.output_reading(output).unwrap();
+            assert_eq!(reading.status(), Status::Error(Error::Bus));
+        }
+        mock.done();
+    }
+}
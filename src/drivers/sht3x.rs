@@ -0,0 +1,509 @@
+//! Sensirion SHT3x (SHT30, SHT31, SHT35) temperature and humidity sensor
+
+use crate::error::Error;
+use crate::hal::I2cDevice;
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    lifecycle::Hooks,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::{self, Timed, Timer},
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use std::time::Duration;
+
+const FETCH_DATA: u16 = 0xE000;
+const STOP_PERIODIC: u16 = 0x3093;
+const SOFT_RESET: u16 = 0x30A2;
+const HEATER_ON: u16 = 0x306D;
+const HEATER_OFF: u16 = 0x3066;
+/// Time the sensor needs after a soft reset before it takes commands again
+const SOFT_RESET_TIME: Duration = Duration::from_micros(1_500);
+
+/// Address selected by the ADDR pin
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Address {
+    Low = 0x44,
+    High = 0x45,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Repeatability {
+    High,
+    Medium,
+    Low,
+}
+
+/// Measurements per second when running in periodic mode
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rate {
+    Half,
+    One,
+    Two,
+    Four,
+    Ten,
+}
+
+impl Rate {
+    /// Time between two measurements
+    pub fn period(&self) -> timer::Duration {
+        timer::Duration::Millis(match self {
+            Rate::Half => 2_000,
+            Rate::One => 1_000,
+            Rate::Two => 500,
+            Rate::Four => 250,
+            Rate::Ten => 100,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Triggers a measurement on every read and waits for it
+    SingleShot(Repeatability),
+    /// The sensor measures on its own and the sensor is read at the same rate
+    /// to fetch each result
+    Periodic(Rate, Repeatability),
+}
+
+impl Mode {
+    fn command(&self) -> u16 {
+        use Repeatability::*;
+        match *self {
+            // Clock stretching disabled
+            Mode::SingleShot(High) => 0x2400,
+            Mode::SingleShot(Medium) => 0x240B,
+            Mode::SingleShot(Low) => 0x2416,
+            Mode::Periodic(Rate::Half, High) => 0x2032,
+            Mode::Periodic(Rate::Half, Medium) => 0x2024,
+            Mode::Periodic(Rate::Half, Low) => 0x202F,
+            Mode::Periodic(Rate::One, High) => 0x2130,
+            Mode::Periodic(Rate::One, Medium) => 0x2126,
+            Mode::Periodic(Rate::One, Low) => 0x212D,
+            Mode::Periodic(Rate::Two, High) => 0x2236,
+            Mode::Periodic(Rate::Two, Medium) => 0x2220,
+            Mode::Periodic(Rate::Two, Low) => 0x222B,
+            Mode::Periodic(Rate::Four, High) => 0x2334,
+            Mode::Periodic(Rate::Four, Medium) => 0x2322,
+            Mode::Periodic(Rate::Four, Low) => 0x2329,
+            Mode::Periodic(Rate::Ten, High) => 0x2737,
+            Mode::Periodic(Rate::Ten, Medium) => 0x2721,
+            Mode::Periodic(Rate::Ten, Low) => 0x272A,
+        }
+    }
+
+    /// Maximum measurement duration from the datasheet
+    fn measurement_time(&self) -> Duration {
+        match self {
+            Mode::SingleShot(Repeatability::High) => Duration::from_micros(15_500),
+            Mode::SingleShot(Repeatability::Medium) => Duration::from_micros(6_500),
+            Mode::SingleShot(Repeatability::Low) => Duration::from_micros(4_500),
+            Mode::Periodic(..) => Duration::ZERO,
+        }
+    }
+}
+
+/// CRC-8 with polynomial 0x31 and initial value 0xFF
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Checks both CRCs and returns the raw temperature and humidity
+fn parse(data: &[u8; 6]) -> Result<(u16, u16), Error> {
+    if crc8(&data[0..2]) != data[2] || crc8(&data[3..5]) != data[5] {
+        return Err(Error::CrcMismatch);
+    }
+    Ok((
+        u16::from_be_bytes([data[0], data[1]]),
+        u16::from_be_bytes([data[3], data[4]]),
+    ))
+}
+
+/// Temperature in celsius from its raw value
+pub fn temperature(raw: u16) -> f64 {
+    -45.0 + 175.0 * raw as f64 / 65535.0
+}
+
+/// Relative humidity from its raw value
+pub fn humidity(raw: u16) -> f64 {
+    100.0 * raw as f64 / 65535.0
+}
+
+/// Module that sets up a single SHT3x on an I2C bus
+pub struct Sht3x {
+    name: String,
+    bus: String,
+    address: Address,
+    mode: Mode,
+    heater: bool,
+}
+
+impl Sht3x {
+    pub fn new(name: &str, bus: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+            address: Address::Low,
+            mode: Mode::SingleShot(Repeatability::High),
+            heater: false,
+        }
+    }
+
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_heater(mut self, heater: bool) -> Self {
+        self.heater = heater;
+        self
+    }
+}
+
+impl Module<SensorHandle<Sht3xSensor>> for Sht3x {
+    fn init(self, robot: &mut Robot) -> SensorHandle<Sht3xSensor> {
+        let device = robot
+            .i2c_device(&self.bus, self.address as u8)
+            .unwrap_or_else(|| panic!("SHT3x needs the {} I2C bus", self.bus));
+        let sensor = Sht3xSensor::new(device, self.mode, self.heater);
+
+        let mut builder = SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_device(&sensor.device)
+            .with_output(OutputType::TEMPERATURE)
+            .with_output(OutputType::HUMIDITY)
+            .with_system(sht3x_reading);
+        if let Mode::Periodic(rate, _) = self.mode {
+            builder.set_custom_timer(Timer::new(rate.period()));
+        }
+        builder.with_component(sensor).build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<Sht3xSensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+
+    /// Resets the sensor on start so it doesn't keep a mode set by an earlier run,
+    /// and stops periodic mode on shutdown
+    fn hooks(sensor: &SensorHandle<Sht3xSensor>) -> Hooks {
+        let start = sensor.clone();
+        let shutdown = sensor.clone();
+        Hooks::new()
+            .on_start(move |robot| {
+                if let Some(mut sensor) = robot.sensor_mut(&start) {
+                    // A sensor that doesn't answer gets reported by its first read
+                    if sensor.soft_reset().is_ok() {
+                        std::thread::sleep(SOFT_RESET_TIME);
+                    }
+                }
+            })
+            .on_shutdown(move |robot| match robot.sensor_mut(&shutdown) {
+                Some(mut sensor) if sensor.periodic_started => sensor.stop(),
+                _ => Ok(()),
+            })
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Sht3xSensor {
+    device: I2cDevice,
+    mode: Mode,
+    heater: bool,
+    // What the device is currently configured as, None until the first read
+    applied_heater: Option<bool>,
+    periodic_started: bool,
+    // Set when the last periodic fetch found no new data
+    missed_fetch: bool,
+}
+
+impl Sht3xSensor {
+    pub fn new(device: I2cDevice, mode: Mode, heater: bool) -> Self {
+        Self {
+            device,
+            mode,
+            heater,
+            applied_heater: None,
+            periodic_started: false,
+            missed_fetch: false,
+        }
+    }
+
+    /// The heater is switched on the next read
+    pub fn set_heater(&mut self, heater: bool) {
+        self.heater = heater;
+    }
+
+    pub fn heater(&self) -> bool {
+        self.heater
+    }
+
+    fn command(&mut self, command: u16) -> Result<(), Error> {
+        self.device.write(&command.to_be_bytes())
+    }
+
+    pub fn soft_reset(&mut self) -> Result<(), Error> {
+        self.periodic_started = false;
+        self.applied_heater = None;
+        self.command(SOFT_RESET)
+    }
+
+    /// Stops periodic mode, the sensor then idles until the next read restarts it
+    pub fn stop(&mut self) -> Result<(), Error> {
+        self.periodic_started = false;
+        self.command(STOP_PERIODIC)
+    }
+
+    /// Returns the temperature in celsius and relative humidity,
+    /// None in periodic mode when the sensor has no new measurement yet
+    pub fn read(&mut self) -> Result<Option<(f64, f64)>, Error> {
+        if self.applied_heater != Some(self.heater) {
+            self.command(if self.heater { HEATER_ON } else { HEATER_OFF })?;
+            self.applied_heater = Some(self.heater);
+        }
+
+        let mut data = [0; 6];
+        match self.mode {
+            Mode::SingleShot(_) => {
+                let mode = self.mode;
+                self.device.exclusive(|bus, address| {
+                    bus.write(address, &mode.command().to_be_bytes())?;
+                    std::thread::sleep(mode.measurement_time());
+                    bus.read(address, &mut data)
+                })?;
+            }
+            Mode::Periodic(..) => {
+                if !self.periodic_started {
+                    self.command(self.mode.command())?;
+                    self.periodic_started = true;
+                }
+                match self.device.write_read(&FETCH_DATA.to_be_bytes(), &mut data) {
+                    // The sensor NACKs the fetch until a measurement is ready,
+                    // missing twice in a row at its own rate means it's gone
+                    Err(Error::Disconnected) if !self.missed_fetch => {
+                        self.missed_fetch = true;
+                        return Ok(None);
+                    }
+                    result => {
+                        result?;
+                        self.missed_fetch = false;
+                    }
+                }
+            }
+        }
+
+        let (t, rh) = parse(&data)?;
+        Ok(Some((temperature(t), humidity(rh))))
+    }
+}
+
+impl Descriptor for Sht3xSensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "sht3x")
+    }
+
+    fn name(&self) -> String {
+        "SHT3x".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Sensirion temperature and humidity sensor".to_string()
+    }
+}
+
+fn sht3x_reading(
+    mut query: Query<(Entity, &mut Sht3xSensor, &Features), Timed>,
+    mut reading: Query<(&mut Reading, &OutputType)>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        // Without a new measurement the last reading stays as it is
+        let Some(result) = sensor.read().transpose() else {
+            continue;
+        };
+        match result {
+            Ok(_) => faults.clear(entity),
+            Err(error) => faults.report(entity, error),
+        }
+
+        for feature in features.iter() {
+            let Ok((mut read, kind)) = reading.get_mut(*feature) else {
+                continue;
+            };
+
+            match result {
+                Ok((t, _)) if *kind == OutputType::TEMPERATURE => {
+                    read.set_as(t, Unit::Celsius);
+                }
+                Ok((_, rh)) if *kind == OutputType::HUMIDITY => {
+                    read.set_as(rh, Unit::RelativeHumidity);
+                }
+                Ok(_) => {}
+                Err(error) => read.fail(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{mock::MockI2c, I2cHandle};
+    use crate::modules::{clock::MockClock, output::Status};
+
+    // Datasheet example, 0xBEEF has a CRC of 0x92
+    #[test]
+    fn crc_matches_datasheet() {
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+        assert_eq!(crc8(&[0x00, 0x00]), 0x81);
+    }
+
+    fn frame(t: u16, rh: u16) -> [u8; 6] {
+        let [t0, t1] = t.to_be_bytes();
+        let [h0, h1] = rh.to_be_bytes();
+        [t0, t1, crc8(&[t0, t1]), h0, h1, crc8(&[h0, h1])]
+    }
+
+    fn outputs(robot: &Robot, sensor: &SensorHandle<Sht3xSensor>) -> (f64, f64, Status) {
+        let t = robot.output_reading(&sensor.outputs()[0]).unwrap();
+        let rh = robot.output_reading(&sensor.outputs()[1]).unwrap();
+        (t.value(), rh.value(), t.status())
+    }
+
+    #[test]
+    fn single_shot_reads() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x45, &[0x30, 0xA2])
+            .expect_write(0x45, &[0x30, 0x66])
+            .expect_write(0x45, &[0x24, 0x00])
+            .expect_read(0x45, &frame(0x6666, 0x8000));
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Sht3x::new("SHT31", "i2c-1").with_address(Address::High));
+        robot.run();
+
+        let (t, rh, status) = outputs(&robot, &sensor);
+        assert!((t - 25.0).abs() < 0.01);
+        assert!((rh - 50.0).abs() < 0.01);
+        assert_eq!(status, Status::Valid);
+        // Nothing to stop in single shot mode
+        assert!(robot.shutdown().is_clean());
+        mock.done();
+    }
+
+    #[test]
+    fn periodic_mode_starts_once() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x44, &[0x30, 0xA2])
+            .expect_write(0x44, &[0x30, 0x6D])
+            .expect_write(0x44, &[0x21, 0x30])
+            .expect_write_read(0x44, &[0xE0, 0x00], &frame(0x6666, 0x8000))
+            .expect_write_read(0x44, &[0xE0, 0x00], &frame(0x0000, 0xFFFF))
+            .expect_write(0x44, &[0x30, 0x93]);
+
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(
+            Sht3x::new("SHT31", "i2c-1")
+                .with_mode(Mode::Periodic(Rate::One, Repeatability::High))
+                .with_heater(true),
+        );
+
+        // Fetched once per measurement
+        for _ in 0..5 {
+            robot.run();
+            clock.advance(std::time::Duration::from_millis(500));
+        }
+
+        let (t, rh, _) = outputs(&robot, &sensor);
+        assert!((t + 45.0).abs() < 0.01);
+        assert!((rh - 100.0).abs() < 0.01);
+        assert!(robot.shutdown().is_clean());
+        mock.done();
+    }
+
+    #[test]
+    fn periodic_mode_waits_for_data() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x44, &[0x30, 0xA2])
+            .expect_write(0x44, &[0x30, 0x66])
+            .expect_write(0x44, &[0x27, 0x37])
+            .expect_write_read(0x44, &[0xE0, 0x00], &frame(0x6666, 0x8000))
+            .expect_error(0x44, Error::Disconnected)
+            .expect_write_read(0x44, &[0xE0, 0x00], &frame(0x6666, 0x8000))
+            .expect_error(0x44, Error::Disconnected)
+            .expect_error(0x44, Error::Disconnected);
+
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(
+            Sht3x::new("SHT31", "i2c-1").with_mode(Mode::Periodic(Rate::Ten, Repeatability::High)),
+        );
+        let step = |robot: &mut Robot| {
+            clock.advance(std::time::Duration::from_millis(100));
+            robot.run();
+        };
+
+        // The first measurement is due a period after the sensor was added
+        step(&mut robot);
+        step(&mut robot);
+        // Fetching before the next measurement is ready keeps the reading
+        step(&mut robot);
+        assert_eq!(outputs(&robot, &sensor).2, Status::Valid);
+        step(&mut robot);
+        step(&mut robot);
+        assert_eq!(outputs(&robot, &sensor).2, Status::Valid);
+
+        // A second miss in a row is a disconnect
+        step(&mut robot);
+        assert_eq!(
+            outputs(&robot, &sensor).2,
+            Status::Error(Error::Disconnected)
+        );
+        mock.done();
+    }
+
+    #[test]
+    fn crc_errors_fault_the_sensor() {
+        let mut data = frame(0x6666, 0x8000);
+        data[2] ^= 0xFF;
+
+        let mock = MockI2c::new();
+        mock.expect_write(0x44, &[0x30, 0xA2])
+            .expect_write(0x44, &[0x30, 0x66])
+            .expect_write(0x44, &[0x24, 0x00])
+            .expect_read(0x44, &data);
+
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let sensor = robot.add(Sht3x::new("SHT31", "i2c-1"));
+        robot.run();
+
+        let (_, _, status) = outputs(&robot, &sensor);
+        assert_eq!(status, Status::Error(Error::CrcMismatch));
+        assert_eq!(robot.faults()[0].1.error, Error::CrcMismatch);
+        mock.done();
+    }
+}
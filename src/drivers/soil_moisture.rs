@@ -0,0 +1,165 @@
+//! Capacitive soil moisture probe read through an ADC channel
+
+use crate::error::Error;
+use crate::hal::{Adc, AdcHandle};
+use crate::modules::{
+    fault::Faults,
+    handle::SensorHandle,
+    output::{OutputType, Reading},
+    sensor::{Features, SensorBuilder},
+    timer::Timed,
+    unit::Unit,
+    Descriptor, Id, Module,
+};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+
+/// Module that sets up a probe on one channel of an ADC
+pub struct SoilMoisture {
+    name: String,
+    bus: String,
+    channel: u8,
+    calibration: Option<(u16, u16)>,
+}
+
+impl SoilMoisture {
+    pub fn new(name: &str, bus: &str, channel: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            bus: bus.to_string(),
+            channel,
+            calibration: None,
+        }
+    }
+
+    /// Raw values read with the probe in air and in water,
+    /// defaults to the ADC's full range with dry at the top
+    pub fn with_calibration(mut self, dry: u16, wet: u16) -> Self {
+        self.calibration = Some((dry, wet));
+        self
+    }
+}
+
+impl Module<SensorHandle<SoilMoistureSensor>> for SoilMoisture {
+    fn init(self, robot: &mut Robot) -> SensorHandle<SoilMoistureSensor> {
+        let adc = robot
+            .bus::<AdcHandle>(&self.bus)
+            .unwrap_or_else(|| panic!("soil moisture probe needs the {} ADC", self.bus));
+        let (dry, wet) = self.calibration.unwrap_or((adc.max_value(), 0));
+        let sensor = SoilMoistureSensor::new(adc, self.channel, dry, wet);
+
+        SensorBuilder::new(&self.name, robot)
+            .with_type(&sensor)
+            .with_output(OutputType::MOISTURE)
+            .with_system(soil_moisture_reading)
+            .with_component(sensor)
+            .build_handle()
+    }
+
+    fn deinit(sensor: SensorHandle<SoilMoistureSensor>, robot: &mut Robot) {
+        let _ = robot.remove_sensor(sensor.entity());
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct SoilMoistureSensor {
+    adc: AdcHandle,
+    channel: u8,
+    dry: u16,
+    wet: u16,
+}
+
+impl SoilMoistureSensor {
+    pub fn new(adc: AdcHandle, channel: u8, dry: u16, wet: u16) -> Self {
+        Self {
+            adc,
+            channel,
+            dry,
+            wet,
+        }
+    }
+
+    /// Returns the moisture in percent, clamped between the calibration points
+    pub fn read(&mut self) -> Result<f64, Error> {
+        let raw = self.adc.read(self.channel)?;
+        if raw > self.adc.max_value() {
+            return Err(Error::OutOfRange);
+        }
+        if self.dry == self.wet {
+            return Ok(0.0);
+        }
+
+        let percent = (self.dry as f64 - raw as f64) / (self.dry as f64 - self.wet as f64) * 100.0;
+        Ok(percent.clamp(0.0, 100.0))
+    }
+}
+
+impl Descriptor for SoilMoistureSensor {
+    fn id(&self) -> Id {
+        Id::new("robotrs", "drivers", "soil_moisture")
+    }
+
+    fn name(&self) -> String {
+        "Soil Moisture".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Capacitive soil moisture probe".to_string()
+    }
+}
+
+fn soil_moisture_reading(
+    mut query: Query<(Entity, &mut SoilMoistureSensor, &Features), Timed>,
+    mut reading: Query<&mut Reading>,
+    mut faults: Faults,
+) {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let Ok(mut read) = reading.get_mut(features[0]) else {
+            continue;
+        };
+
+        match sensor.read() {
+            Ok(value) => {
+                read.set_as(value, Unit::Percent);
+                faults.clear(entity);
+            }
+            Err(error) => {
+                read.fail(error);
+                faults.report(entity, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mock::MockAdc;
+
+    #[test]
+    fn maps_calibration_to_percent() {
+        let adc = MockAdc::new(12);
+        let mut robot = Robot::new().with_bus("adc", AdcHandle::new(adc.clone()));
+        let sensor = robot.add(SoilMoisture::new("Pot", "adc", 2).with_calibration(3000, 1000));
+        let feature = sensor.output(OutputType::MOISTURE).unwrap();
+        let mut moisture = |raw: u16| {
+            adc.set(2, raw);
+            robot.run();
+            robot.output_reading(&feature).unwrap().value()
+        };
+
+        assert_eq!(moisture(2000), 50.0);
+        assert_eq!(moisture(3500), 0.0);
+        assert_eq!(moisture(500), 100.0);
+    }
+
+    #[test]
+    fn unread_channel_faults() {
+        let adc = MockAdc::new(10);
+        let mut robot = Robot::new().with_bus("adc", AdcHandle::new(adc));
+        robot.add(SoilMoisture::new("Pot", "adc", 0));
+        robot.run();
+
+        assert_eq!(robot.faults()[0].1.error, Error::Disconnected);
+    }
+}
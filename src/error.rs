@@ -0,0 +1,86 @@
+use crate::modules::output::OutputType;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors a sensor or its bus can run into
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Error {
+    /// The bus transaction failed
+    Bus,
+    /// The device didn't answer in time
+    Timeout,
+    /// The data's checksum didn't match
+    CrcMismatch,
+    /// The device answered with a value it can't produce
+    OutOfRange,
+    /// The device is no longer reachable
+    Disconnected,
+    /// Another sensor already uses this I2C address on the same bus
+    AddressInUse(u8),
+    /// Another Descriptor type already uses the same id,
+    /// or an output type was registered again with a different definition.
+    /// Holds the name of the type that owns the id
+    DuplicateId(&'static str),
+    /// Another sensor already uses this name
+    DuplicateName,
+    /// Every output type id is taken
+    TooManyOutputTypes,
+    /// The entity isn't a sensor
+    NotASensor,
+    /// The entity isn't a sensor's output
+    NotAnOutput,
+    /// The entity isn't an actuator
+    NotAnActuator,
+    /// No sensor goes by this name
+    UnknownSensor,
+    /// The sensor has no output of this type
+    MissingOutput(OutputType),
+    /// The sensor wasn't built around a component of this type
+    MissingComponent(&'static str),
+    /// The operating system refused the operation
+    Io(std::io::ErrorKind),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Bus => f.write_str("bus error"),
+            Error::Timeout => f.write_str("device timed out"),
+            Error::CrcMismatch => f.write_str("CRC mismatch"),
+            Error::OutOfRange => f.write_str("value out of range"),
+            Error::Disconnected => f.write_str("device disconnected"),
+            Error::AddressInUse(address) => {
+                write!(f, "I2C address {:#04x} already in use", address)
+            }
+            Error::DuplicateId(owner) => write!(f, "id already used by {}", owner),
+            Error::DuplicateName => f.write_str("name already used by another sensor"),
+            Error::TooManyOutputTypes => f.write_str("no output type ids left"),
+            Error::NotASensor => f.write_str("entity is not a sensor"),
+            Error::NotAnOutput => f.write_str("entity is not an output"),
+            Error::NotAnActuator => f.write_str("entity is not an actuator"),
+            Error::UnknownSensor => f.write_str("no sensor with that name"),
+            Error::MissingOutput(kind) => {
+                write!(f, "sensor has no output of type {}", kind.id())
+            }
+            Error::MissingComponent(name) => write!(f, "sensor has no {} component", name),
+            Error::Io(kind) => write!(f, "io error: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+
+        match error.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Error::Timeout,
+            ErrorKind::NotFound | ErrorKind::BrokenPipe | ErrorKind::NotConnected => {
+                Error::Disconnected
+            }
+            kind => Error::Io(kind),
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Linux userspace backends, enabled with the `linux` feature
+//! Each function opens the device at the given path so every module can point at its own bus
+
+use crate::error::Error;
+use crate::hal::{I2cHandle, InputPinHandle, OutputPinHandle, Serial, SerialHandle, SpiHandle};
+use crate::Robot;
+use embedded_hal::{digital, i2c, spi};
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use i2cdev::core::{I2CMessage, I2CTransfer};
+use i2cdev::linux::{I2CMessageFlags, LinuxI2CBus, LinuxI2CError, LinuxI2CMessage};
+use nix::errno::Errno;
+use spidev::{SpiModeFlags, Spidev, SpidevOptions, SpidevTransfer};
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+const CONSUMER: &str = "robotrs";
+
+/// Opens an I2C bus like /dev/i2c-1
+pub fn i2c(path: &str) -> Result<I2cHandle, Error> {
+    let bus = LinuxI2CBus::new(path).map_err(i2c_error)?;
+    Ok(I2cHandle::new(LinuxI2c(bus)))
+}
+
+/// Opens an SPI device like /dev/spidev0.0 in mode 0
+pub fn spi(path: &str, max_speed_hz: u32) -> Result<SpiHandle, Error> {
+    let mut device = Spidev::open(path)?;
+    let options = SpidevOptions::new()
+        .bits_per_word(8)
+        .max_speed_hz(max_speed_hz)
+        .mode(SpiModeFlags::SPI_MODE_0)
+        .build();
+    device.configure(&options)?;
+    Ok(SpiHandle::new(LinuxSpi(device)))
+}
+
+/// Requests a line of a GPIO character device like /dev/gpiochip0 as an input
+pub fn gpio_input(chip: &str, line: u32) -> Result<InputPinHandle, Error> {
+    let pin = request_line(chip, line, LineRequestFlags::INPUT, 0)?;
+    Ok(InputPinHandle::new(pin))
+}
+
+/// Requests a line of a GPIO character device like /dev/gpiochip0 as an output
+pub fn gpio_output(chip: &str, line: u32, high: bool) -> Result<OutputPinHandle, Error> {
+    let pin = request_line(chip, line, LineRequestFlags::OUTPUT, high as u8)?;
+    Ok(OutputPinHandle::new(pin))
+}
+
+fn request_line(
+    chip: &str,
+    line: u32,
+    flags: LineRequestFlags,
+    value: u8,
+) -> Result<LinuxPin, Error> {
+    Chip::new(chip)
+        .and_then(|mut chip| chip.get_line(line))
+        .and_then(|line| line.request(flags, value, CONSUMER))
+        .map(LinuxPin)
+        .map_err(gpio_error)
+}
+
+/// Opens a serial tty like /dev/ttyUSB0
+pub fn serial(path: &str, baud_rate: u32) -> Result<SerialHandle, Error> {
+    let port = serialport::new(path, baud_rate)
+        .timeout(Duration::from_millis(10))
+        .open()
+        .map_err(|e| Error::from(std::io::Error::from(e)))?;
+    Ok(SerialHandle::new(IoSerial(port)))
+}
+
+/// The kernel reports a missing acknowledge as ENXIO or EREMOTEIO
+fn i2c_error(error: LinuxI2CError) -> Error {
+    let error = std::io::Error::from(error);
+    match error.raw_os_error().map(Errno::from_i32) {
+        Some(Errno::ENXIO | Errno::EREMOTEIO) => Error::Disconnected,
+        _ => error.into(),
+    }
+}
+
+/// GPIO errors carry either an io::Error or the errno of the failed ioctl
+fn gpio_error(error: gpio_cdev::Error) -> Error {
+    let source = std::error::Error::source(&error);
+    if let Some(errno) = source.and_then(|e| e.downcast_ref::<Errno>()) {
+        return Error::from(std::io::Error::from(*errno));
+    }
+    match source.and_then(|e| e.downcast_ref::<std::io::Error>()) {
+        Some(e) => Error::from(std::io::Error::from(e.kind())),
+        None => Error::Bus,
+    }
+}
+
+/// I2C bus behind an i2c-dev file, each transaction is a single I2C_RDWR call
+pub struct LinuxI2c(pub LinuxI2CBus);
+
+impl i2c::ErrorType for LinuxI2c {
+    type Error = Error;
+}
+
+impl i2c::I2c for LinuxI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Error> {
+        if operations.is_empty() {
+            return Ok(());
+        }
+
+        let mut messages = Vec::with_capacity(operations.len());
+        let mut previous = None;
+        for operation in operations.iter_mut() {
+            let (message, read) = match operation {
+                i2c::Operation::Read(buffer) => (LinuxI2CMessage::read(buffer), true),
+                i2c::Operation::Write(data) => (LinuxI2CMessage::write(data), false),
+            };
+            let mut message = message.with_address(address.into());
+            // Operations in the same direction as the last one continue without a repeated start
+            if previous == Some(read) {
+                let mut flags = I2CMessageFlags::NO_START;
+                flags.set(I2CMessageFlags::READ, read);
+                message = message.with_flags(flags);
+            }
+            messages.push(message);
+            previous = Some(read);
+        }
+
+        self.0.transfer(&mut messages).map_err(i2c_error)?;
+        Ok(())
+    }
+}
+
+/// SPI device behind a spidev file, each transaction is a single SPI_IOC_MESSAGE call
+pub struct LinuxSpi(pub Spidev);
+
+impl spi::ErrorType for LinuxSpi {
+    type Error = Error;
+}
+
+impl spi::SpiDevice for LinuxSpi {
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) -> Result<(), Error> {
+        // spidev has no in place transfers, the data to send is copied out first
+        let sent: Vec<Vec<u8>> = operations
+            .iter()
+            .map(|operation| match operation {
+                spi::Operation::TransferInPlace(buffer) => buffer.to_vec(),
+                _ => vec![],
+            })
+            .collect();
+
+        let mut transfers = Vec::with_capacity(operations.len());
+        for (operation, sent) in operations.iter_mut().zip(&sent) {
+            match operation {
+                spi::Operation::Read(buffer) => transfers.push(SpidevTransfer::read(buffer)),
+                spi::Operation::Write(data) => transfers.push(SpidevTransfer::write(data)),
+                spi::Operation::Transfer(read, write) => {
+                    // Both sides of a spidev transfer have the same length,
+                    // whatever one side has left is clocked on its own
+                    let shared = read.len().min(write.len());
+                    let (read, read_rest) = read.split_at_mut(shared);
+                    let (write, write_rest) = write.split_at(shared);
+                    transfers.push(SpidevTransfer::read_write(write, read));
+                    if !read_rest.is_empty() {
+                        transfers.push(SpidevTransfer::read(read_rest));
+                    }
+                    if !write_rest.is_empty() {
+                        transfers.push(SpidevTransfer::write(write_rest));
+                    }
+                }
+                spi::Operation::TransferInPlace(buffer) => {
+                    transfers.push(SpidevTransfer::read_write(sent, buffer))
+                }
+                spi::Operation::DelayNs(ns) => {
+                    let us = ns.div_ceil(1_000).min(u16::MAX as u32) as u16;
+                    transfers.push(SpidevTransfer::delay(us));
+                }
+            }
+        }
+
+        Ok(self.0.transfer_multiple(&mut transfers)?)
+    }
+}
+
+/// Line of a GPIO character device
+pub struct LinuxPin(pub LineHandle);
+
+impl digital::ErrorType for LinuxPin {
+    type Error = Error;
+}
+
+impl digital::InputPin for LinuxPin {
+    fn is_high(&mut self) -> Result<bool, Error> {
+        Ok(self.0.get_value().map_err(gpio_error)? != 0)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl digital::OutputPin for LinuxPin {
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.0.set_value(0).map_err(gpio_error)
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.0.set_value(1).map_err(gpio_error)
+    }
+}
+
+/// Serial port over anything that implements std's Read and Write,
+/// reads that time out are treated as having nothing available
+pub struct IoSerial<T>(pub T);
+
+impl<T: Read + Write + Send> Serial for IoSerial<T> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        Ok(self.0.write(data)?)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        match self.0.read(buffer) {
+            Ok(read) => Ok(read),
+            Err(e) if matches!(e.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.0.flush()?)
+    }
+}
+
+impl Robot {
+    /// Opens and registers an I2C bus
+    pub fn add_linux_i2c(&mut self, name: &str, path: &str) -> Result<(), Error> {
+        self.add_bus(name, i2c(path)?);
+        Ok(())
+    }
+
+    /// Opens and registers an SPI device
+    pub fn add_linux_spi(
+        &mut self,
+        name: &str,
+        path: &str,
+        max_speed_hz: u32,
+    ) -> Result<(), Error> {
+        self.add_bus(name, spi(path, max_speed_hz)?);
+        Ok(())
+    }
+
+    /// Requests and registers a GPIO line as an input
+    pub fn add_linux_gpio_input(&mut self, name: &str, chip: &str, line: u32) -> Result<(), Error> {
+        self.add_bus(name, gpio_input(chip, line)?);
+        Ok(())
+    }
+
+    /// Requests and registers a GPIO line as an output starting at the given level
+    pub fn add_linux_gpio_output(
+        &mut self,
+        name: &str,
+        chip: &str,
+        line: u32,
+        high: bool,
+    ) -> Result<(), Error> {
+        self.add_bus(name, gpio_output(chip, line, high)?);
+        Ok(())
+    }
+
+    /// Opens and registers a serial port
+    pub fn add_linux_serial(
+        &mut self,
+        name: &str,
+        path: &str,
+        baud_rate: u32,
+    ) -> Result<(), Error> {
+        self.add_bus(name, serial(path, baud_rate)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn serial_over_loopback() {
+        let (device, remote) = UnixStream::pair().unwrap();
+        device.set_nonblocking(true).unwrap();
+        let mut remote = IoSerial(remote);
+        let mut port = SerialHandle::new(IoSerial(device));
+
+        let mut buffer = [0; 4];
+        assert_eq!(port.read(&mut buffer), Ok(0));
+
+        remote.write(b"ping").unwrap();
+        assert_eq!(port.read(&mut buffer), Ok(4));
+        assert_eq!(&buffer, b"ping");
+
+        port.write(b"pong").unwrap();
+        port.flush().unwrap();
+        assert_eq!(remote.read(&mut buffer), Ok(4));
+        assert_eq!(&buffer, b"pong");
+    }
+
+    #[test]
+    fn missing_devices_fail_to_open() {
+        let mut robot = Robot::new();
+        assert_eq!(
+            robot.add_linux_i2c("i2c", "/dev/robotrs-missing-i2c"),
+            Err(Error::Disconnected)
+        );
+        assert_eq!(
+            robot.add_linux_spi("spi", "/dev/robotrs-missing-spi", 1_000_000),
+            Err(Error::Disconnected)
+        );
+        assert_eq!(
+            robot.add_linux_gpio_input("button", "/dev/robotrs-missing-gpiochip", 0),
+            Err(Error::Disconnected)
+        );
+        assert_eq!(
+            robot.add_linux_gpio_output("led", "/dev/robotrs-missing-gpiochip", 0, false),
+            Err(Error::Disconnected)
+        );
+        assert_eq!(
+            robot.add_linux_serial("tty", "/dev/robotrs-missing-tty", 9600),
+            Err(Error::Disconnected)
+        );
+        assert!(robot.bus::<I2cHandle>("i2c").is_none());
+    }
+}
@@ -0,0 +1,482 @@
+//! In memory buses used to test modules without hardware,
+//! clones share their state so a test can keep one to drive or inspect the bus
+
+use crate::error::Error;
+use crate::hal::{Adc, OneWire, Serial};
+use embedded_hal::{digital, i2c, spi};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Expected I2C operation
+#[derive(Clone, PartialEq, Debug)]
+pub enum I2cTransaction {
+    Write { address: u8, data: Vec<u8> },
+    Read { address: u8, data: Vec<u8> },
+    Fail { address: u8, error: Error },
+}
+
+/// I2C bus that replays a script of expected operations,
+/// panics when the driver doesn't follow it
+#[derive(Clone, Default)]
+pub struct MockI2c {
+    expected: Arc<Mutex<VecDeque<I2cTransaction>>>,
+}
+
+impl MockI2c {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(&self, transaction: I2cTransaction) -> &Self {
+        self.expected.lock().unwrap().push_back(transaction);
+        self
+    }
+
+    pub fn expect_write(&self, address: u8, data: &[u8]) -> &Self {
+        self.expect(I2cTransaction::Write {
+            address,
+            data: data.to_vec(),
+        })
+    }
+
+    pub fn expect_read(&self, address: u8, data: &[u8]) -> &Self {
+        self.expect(I2cTransaction::Read {
+            address,
+            data: data.to_vec(),
+        })
+    }
+
+    pub fn expect_write_read(&self, address: u8, write: &[u8], read: &[u8]) -> &Self {
+        self.expect_write(address, write).expect_read(address, read)
+    }
+
+    /// The next operation on the address fails
+    pub fn expect_error(&self, address: u8, error: Error) -> &Self {
+        self.expect(I2cTransaction::Fail { address, error })
+    }
+
+    /// Panics if some expected operations were never done
+    pub fn done(&self) {
+        let expected = self.expected.lock().unwrap();
+        assert!(
+            expected.is_empty(),
+            "unused I2C expectations {:?}",
+            expected
+        );
+    }
+}
+
+impl i2c::ErrorType for MockI2c {
+    type Error = Error;
+}
+
+impl i2c::I2c for MockI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Error> {
+        let mut expected = self.expected.lock().unwrap();
+        for operation in operations {
+            let next = expected
+                .pop_front()
+                .unwrap_or_else(|| panic!("unexpected I2C operation {:?}", operation));
+
+            match (operation, next) {
+                (_, I2cTransaction::Fail { address: a, error }) if a == address => {
+                    return Err(error)
+                }
+                (
+                    i2c::Operation::Write(data),
+                    I2cTransaction::Write {
+                        address: a,
+                        data: d,
+                    },
+                ) if a == address => {
+                    assert_eq!(*data, d.as_slice(), "unexpected I2C write to {:#x}", a);
+                }
+                (i2c::Operation::Read(buffer), I2cTransaction::Read { address: a, data })
+                    if a == address =>
+                {
+                    assert_eq!(buffer.len(), data.len(), "unexpected I2C read length");
+                    buffer.copy_from_slice(&data);
+                }
+                (operation, next) => panic!(
+                    "expected {:?} but got {:?} on {:#x}",
+                    next, operation, address
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Expected SPI operation, transfers are a write followed by a read
+#[derive(Clone, PartialEq, Debug)]
+pub enum SpiTransaction {
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+    Fail(Error),
+}
+
+/// SPI device that replays a script of expected operations,
+/// panics when the driver doesn't follow it
+#[derive(Clone, Default)]
+pub struct MockSpi {
+    expected: Arc<Mutex<VecDeque<SpiTransaction>>>,
+}
+
+impl MockSpi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(&self, transaction: SpiTransaction) -> &Self {
+        self.expected.lock().unwrap().push_back(transaction);
+        self
+    }
+
+    pub fn expect_write(&self, data: &[u8]) -> &Self {
+        self.expect(SpiTransaction::Write(data.to_vec()))
+    }
+
+    pub fn expect_read(&self, data: &[u8]) -> &Self {
+        self.expect(SpiTransaction::Read(data.to_vec()))
+    }
+
+    pub fn expect_transfer(&self, write: &[u8], read: &[u8]) -> &Self {
+        self.expect_write(write).expect_read(read)
+    }
+
+    /// Panics if some expected operations were never done
+    pub fn done(&self) {
+        let expected = self.expected.lock().unwrap();
+        assert!(
+            expected.is_empty(),
+            "unused SPI expectations {:?}",
+            expected
+        );
+    }
+
+    fn next(expected: &mut VecDeque<SpiTransaction>) -> SpiTransaction {
+        expected
+            .pop_front()
+            .unwrap_or_else(|| panic!("unexpected SPI operation"))
+    }
+
+    fn write(expected: &mut VecDeque<SpiTransaction>, data: &[u8]) -> Result<(), Error> {
+        match Self::next(expected) {
+            SpiTransaction::Write(d) => {
+                assert_eq!(data, d.as_slice(), "unexpected SPI write");
+                Ok(())
+            }
+            SpiTransaction::Fail(error) => Err(error),
+            next => panic!("expected {:?} but got a write", next),
+        }
+    }
+
+    fn read(expected: &mut VecDeque<SpiTransaction>, buffer: &mut [u8]) -> Result<(), Error> {
+        match Self::next(expected) {
+            SpiTransaction::Read(data) => {
+                assert_eq!(buffer.len(), data.len(), "unexpected SPI read length");
+                buffer.copy_from_slice(&data);
+                Ok(())
+            }
+            SpiTransaction::Fail(error) => Err(error),
+            next => panic!("expected {:?} but got a read", next),
+        }
+    }
+}
+
+impl spi::ErrorType for MockSpi {
+    type Error = Error;
+}
+
+impl spi::SpiDevice for MockSpi {
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) -> Result<(), Error> {
+        let mut expected = self.expected.lock().unwrap();
+        for operation in operations {
+            match operation {
+                spi::Operation::Write(data) => Self::write(&mut expected, data)?,
+                spi::Operation::Read(buffer) => Self::read(&mut expected, buffer)?,
+                spi::Operation::Transfer(read, write) => {
+                    Self::write(&mut expected, write)?;
+                    Self::read(&mut expected, read)?;
+                }
+                spi::Operation::TransferInPlace(buffer) => {
+                    Self::write(&mut expected, buffer)?;
+                    Self::read(&mut expected, buffer)?;
+                }
+                spi::Operation::DelayNs(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// GPIO pin that can be used as both input and output
+#[derive(Clone, Default)]
+pub struct MockPin {
+    high: Arc<Mutex<bool>>,
+}
+
+impl MockPin {
+    pub fn new(high: bool) -> Self {
+        Self {
+            high: Arc::new(Mutex::new(high)),
+        }
+    }
+
+    pub fn set(&self, high: bool) {
+        *self.high.lock().unwrap() = high;
+    }
+
+    pub fn is_set_high(&self) -> bool {
+        *self.high.lock().unwrap()
+    }
+}
+
+impl digital::ErrorType for MockPin {
+    type Error = Error;
+}
+
+impl digital::InputPin for MockPin {
+    fn is_high(&mut self) -> Result<bool, Error> {
+        Ok(self.is_set_high())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Error> {
+        Ok(!self.is_set_high())
+    }
+}
+
+impl digital::OutputPin for MockPin {
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.set(true);
+        Ok(())
+    }
+}
+
+/// Serial port with an incoming buffer filled by the test
+/// and an outgoing buffer the test can inspect
+#[derive(Clone, Default)]
+pub struct MockSerial {
+    rx: Arc<Mutex<VecDeque<u8>>>,
+    tx: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MockSerial {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues bytes for the driver to read
+    pub fn receive(&self, data: &[u8]) {
+        self.rx.lock().unwrap().extend(data);
+    }
+
+    /// Takes every byte the driver wrote
+    pub fn sent(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.tx.lock().unwrap())
+    }
+}
+
+impl Serial for MockSerial {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.tx.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut rx = self.rx.lock().unwrap();
+        let len = buffer.len().min(rx.len());
+        for (byte, received) in buffer.iter_mut().zip(rx.drain(..len)) {
+            *byte = received;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Expected 1-Wire operation
+#[derive(Clone, PartialEq, Debug)]
+pub enum OneWireTransaction {
+    Reset { presence: bool },
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+}
+
+/// 1-Wire bus that replays a script of expected operations,
+/// panics when the driver doesn't follow it
+#[derive(Clone, Default)]
+pub struct MockOneWire {
+    expected: Arc<Mutex<VecDeque<OneWireTransaction>>>,
+}
+
+impl MockOneWire {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(&self, transaction: OneWireTransaction) -> &Self {
+        let mut expected = self.expected.lock().unwrap();
+        // Split multi byte operations so they can be consumed a byte at a time
+        match transaction {
+            OneWireTransaction::Write(data) => {
+                expected.extend(data.into_iter().map(|b| OneWireTransaction::Write(vec![b])))
+            }
+            OneWireTransaction::Read(data) => {
+                expected.extend(data.into_iter().map(|b| OneWireTransaction::Read(vec![b])))
+            }
+            reset => expected.push_back(reset),
+        }
+        self
+    }
+
+    pub fn expect_reset(&self, presence: bool) -> &Self {
+        self.expect(OneWireTransaction::Reset { presence })
+    }
+
+    pub fn expect_write(&self, data: &[u8]) -> &Self {
+        self.expect(OneWireTransaction::Write(data.to_vec()))
+    }
+
+    pub fn expect_read(&self, data: &[u8]) -> &Self {
+        self.expect(OneWireTransaction::Read(data.to_vec()))
+    }
+
+    /// Panics if some expected operations were never done
+    pub fn done(&self) {
+        let expected = self.expected.lock().unwrap();
+        assert!(
+            expected.is_empty(),
+            "unused 1-Wire expectations {:?}",
+            expected
+        );
+    }
+
+    fn next(&self) -> OneWireTransaction {
+        self.expected
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| panic!("unexpected 1-Wire operation"))
+    }
+}
+
+impl OneWire for MockOneWire {
+    fn reset(&mut self) -> Result<bool, Error> {
+        match self.next() {
+            OneWireTransaction::Reset { presence } => Ok(presence),
+            next => panic!("expected {:?} but got a reset", next),
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+        match self.next() {
+            OneWireTransaction::Write(data) => {
+                assert_eq!(data, [byte], "unexpected 1-Wire write");
+                Ok(())
+            }
+            next => panic!("expected {:?} but got a write of {:#04x}", next, byte),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        match self.next() {
+            OneWireTransaction::Read(data) => Ok(data[0]),
+            next => panic!("expected {:?} but got a read", next),
+        }
+    }
+}
+
+/// ADC whose channels are set by the test
+#[derive(Clone)]
+pub struct MockAdc {
+    channels: Arc<Mutex<HashMap<u8, Result<u16, Error>>>>,
+    max_value: u16,
+}
+
+impl MockAdc {
+    pub fn new(bits: u8) -> Self {
+        Self {
+            channels: Default::default(),
+            max_value: ((1u32 << bits) - 1) as u16,
+        }
+    }
+
+    pub fn set(&self, channel: u8, value: u16) {
+        self.channels.lock().unwrap().insert(channel, Ok(value));
+    }
+
+    pub fn fail(&self, channel: u8, error: Error) {
+        self.channels.lock().unwrap().insert(channel, Err(error));
+    }
+}
+
+impl Adc for MockAdc {
+    fn read(&mut self, channel: u8) -> Result<u16, Error> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(&channel)
+            .copied()
+            .unwrap_or(Err(Error::Disconnected))
+    }
+
+    fn max_value(&self) -> u16 {
+        self.max_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{AdcHandle, I2cHandle};
+    use crate::Robot;
+    use embedded_hal::i2c::I2c;
+
+    #[test]
+    fn i2c_replays_script() {
+        let mock = MockI2c::new();
+        mock.expect_write_read(0x44, &[0x24, 0x00], &[0x01, 0x02]);
+
+        let mut bus = I2cHandle::new(mock.clone());
+        let mut buffer = [0; 2];
+        bus.write_read(0x44, &[0x24, 0x00], &mut buffer).unwrap();
+
+        assert_eq!(buffer, [0x01, 0x02]);
+        mock.done();
+    }
+
+    #[test]
+    fn i2c_replays_errors() {
+        let mock = MockI2c::new();
+        mock.expect_error(0x44, Error::Timeout);
+
+        let mut bus = I2cHandle::new(mock);
+        assert_eq!(bus.write(0x44, &[0x00]), Err(Error::Timeout));
+    }
+
+    #[test]
+    fn robot_shares_buses() {
+        let adc = MockAdc::new(12);
+        let robot = Robot::new().with_bus("adc", AdcHandle::new(adc.clone()));
+
+        let mut handle = robot.bus::<AdcHandle>("adc").unwrap();
+        adc.set(3, 1024);
+
+        assert_eq!(handle.read(3), Ok(1024));
+        assert_eq!(handle.max_value(), 4095);
+        assert!(robot.bus::<AdcHandle>("missing").is_none());
+        assert!(robot.bus::<I2cHandle>("adc").is_none());
+    }
+}
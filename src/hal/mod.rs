@@ -0,0 +1,389 @@
+//! Bus abstractions shared between modules
+//! I2C, SPI and GPIO are built on top of embedded-hal,
+//! serial ports and ADCs have no embedded-hal 1.0 trait so they get their own
+
+use crate::error::Error;
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use embedded_hal::{digital, i2c, spi};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "linux")]
+pub mod linux;
+pub mod mock;
+mod shared;
+
+pub use shared::I2cDevice;
+
+/// Byte oriented serial port
+pub trait Serial: Send {
+    /// Writes as many bytes as possible, returning the amount written
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error>;
+    /// Reads the available bytes, returning the amount read
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error>;
+    fn flush(&mut self) -> Result<(), Error>;
+}
+
+/// Dallas 1-Wire bus master
+pub trait OneWire: Send {
+    /// Resets the bus, returns true if a device answered with a presence pulse
+    fn reset(&mut self) -> Result<bool, Error>;
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error>;
+    fn read_byte(&mut self) -> Result<u8, Error>;
+}
+
+/// Analog to digital converter with one or more channels
+pub trait Adc: Send {
+    fn read(&mut self, channel: u8) -> Result<u16, Error>;
+    /// Highest raw value the converter can return
+    fn max_value(&self) -> u16;
+}
+
+impl Error {
+    /// Maps a HAL's I2C error by its kind, the crate's own Error is kept as is
+    pub fn from_i2c<E: i2c::Error + 'static>(error: E) -> Self {
+        if let Some(error) = Self::downcast(&error) {
+            return error;
+        }
+        match error.kind() {
+            i2c::ErrorKind::NoAcknowledge(_) => Error::Disconnected,
+            _ => Error::Bus,
+        }
+    }
+
+    pub fn from_spi<E: spi::Error + 'static>(error: E) -> Self {
+        Self::downcast(&error).unwrap_or(Error::Bus)
+    }
+
+    pub fn from_digital<E: digital::Error + 'static>(error: E) -> Self {
+        Self::downcast(&error).unwrap_or(Error::Bus)
+    }
+
+    // Buses written against the crate, like the mocks, already return an Error
+    fn downcast<E: 'static>(error: &E) -> Option<Self> {
+        (error as &dyn Any).downcast_ref::<Error>().copied()
+    }
+}
+
+impl i2c::Error for Error {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            Error::Bus => i2c::ErrorKind::Bus,
+            Error::Disconnected => i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Unknown),
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl spi::Error for Error {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl digital::Error for Error {
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
+
+/// Wraps a bus so its errors are turned into the crate's Error
+struct MapErr<T>(T);
+
+impl<T: i2c::I2c> i2c::ErrorType for MapErr<T> {
+    type Error = Error;
+}
+
+impl<T: i2c::I2c> i2c::I2c for MapErr<T>
+where
+    T::Error: 'static,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Error> {
+        self.0
+            .transaction(address, operations)
+            .map_err(Error::from_i2c)
+    }
+}
+
+impl<T: spi::SpiDevice> spi::ErrorType for MapErr<T> {
+    type Error = Error;
+}
+
+impl<T: spi::SpiDevice> spi::SpiDevice for MapErr<T>
+where
+    T::Error: 'static,
+{
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) -> Result<(), Error> {
+        self.0.transaction(operations).map_err(Error::from_spi)
+    }
+}
+
+struct InputErr<T>(T);
+
+impl<T: digital::InputPin> digital::ErrorType for InputErr<T> {
+    type Error = Error;
+}
+
+impl<T: digital::InputPin> digital::InputPin for InputErr<T>
+where
+    T::Error: 'static,
+{
+    fn is_high(&mut self) -> Result<bool, Error> {
+        self.0.is_high().map_err(Error::from_digital)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Error> {
+        self.0.is_low().map_err(Error::from_digital)
+    }
+}
+
+struct OutputErr<T>(T);
+
+impl<T: digital::OutputPin> digital::ErrorType for OutputErr<T> {
+    type Error = Error;
+}
+
+impl<T: digital::OutputPin> digital::OutputPin for OutputErr<T>
+where
+    T::Error: 'static,
+{
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.0.set_low().map_err(Error::from_digital)
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.0.set_high().map_err(Error::from_digital)
+    }
+}
+
+type Shared<T> = Arc<Mutex<T>>;
+
+fn lock<T: ?Sized>(shared: &Mutex<T>) -> Result<std::sync::MutexGuard<'_, T>, Error> {
+    shared.lock().map_err(|_| Error::Bus)
+}
+
+/// Cloneable handle to a shared I2C bus
+#[derive(Clone)]
+pub struct I2cHandle(Shared<dyn i2c::I2c<Error = Error> + Send>);
+
+impl I2cHandle {
+    pub fn new<T: i2c::I2c + Send + 'static>(bus: T) -> Self {
+        Self(Arc::new(Mutex::new(MapErr(bus))))
+    }
+}
+
+impl i2c::ErrorType for I2cHandle {
+    type Error = Error;
+}
+
+impl i2c::I2c for I2cHandle {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Error> {
+        lock(&self.0)?.transaction(address, operations)
+    }
+}
+
+/// Cloneable handle to a shared SPI device
+#[derive(Clone)]
+pub struct SpiHandle(Shared<dyn spi::SpiDevice<u8, Error = Error> + Send>);
+
+impl SpiHandle {
+    pub fn new<T: spi::SpiDevice + Send + 'static>(device: T) -> Self {
+        Self(Arc::new(Mutex::new(MapErr(device))))
+    }
+}
+
+impl spi::ErrorType for SpiHandle {
+    type Error = Error;
+}
+
+impl spi::SpiDevice for SpiHandle {
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, u8>]) -> Result<(), Error> {
+        lock(&self.0)?.transaction(operations)
+    }
+}
+
+/// Cloneable handle to a shared input pin
+#[derive(Clone)]
+pub struct InputPinHandle(Shared<dyn digital::InputPin<Error = Error> + Send>);
+
+impl InputPinHandle {
+    pub fn new<T: digital::InputPin + Send + 'static>(pin: T) -> Self {
+        Self(Arc::new(Mutex::new(InputErr(pin))))
+    }
+}
+
+impl digital::ErrorType for InputPinHandle {
+    type Error = Error;
+}
+
+impl digital::InputPin for InputPinHandle {
+    fn is_high(&mut self) -> Result<bool, Error> {
+        lock(&self.0)?.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Error> {
+        lock(&self.0)?.is_low()
+    }
+}
+
+/// Cloneable handle to a shared output pin
+#[derive(Clone)]
+pub struct OutputPinHandle(Shared<dyn digital::OutputPin<Error = Error> + Send>);
+
+impl OutputPinHandle {
+    pub fn new<T: digital::OutputPin + Send + 'static>(pin: T) -> Self {
+        Self(Arc::new(Mutex::new(OutputErr(pin))))
+    }
+}
+
+impl digital::ErrorType for OutputPinHandle {
+    type Error = Error;
+}
+
+impl digital::OutputPin for OutputPinHandle {
+    fn set_low(&mut self) -> Result<(), Error> {
+        lock(&self.0)?.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        lock(&self.0)?.set_high()
+    }
+}
+
+/// Cloneable handle to a shared serial port
+#[derive(Clone)]
+pub struct SerialHandle(Shared<dyn Serial>);
+
+impl SerialHandle {
+    pub fn new<T: Serial + 'static>(port: T) -> Self {
+        Self(Arc::new(Mutex::new(port)))
+    }
+}
+
+impl Serial for SerialHandle {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        lock(&self.0)?.write(data)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        lock(&self.0)?.read(buffer)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        lock(&self.0)?.flush()
+    }
+}
+
+/// Cloneable handle to a shared 1-Wire bus
+#[derive(Clone)]
+pub struct OneWireHandle(Shared<dyn OneWire>);
+
+impl OneWireHandle {
+    pub fn new<T: OneWire + 'static>(bus: T) -> Self {
+        Self(Arc::new(Mutex::new(bus)))
+    }
+
+    /// Holds the bus for a whole reset, command and response sequence
+    pub fn exclusive<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn OneWire) -> Result<T, Error>,
+    {
+        let mut bus = lock(&self.0)?;
+        f(&mut *bus)
+    }
+}
+
+impl OneWire for OneWireHandle {
+    fn reset(&mut self) -> Result<bool, Error> {
+        lock(&self.0)?.reset()
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), Error> {
+        lock(&self.0)?.write_byte(byte)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        lock(&self.0)?.read_byte()
+    }
+}
+
+/// Cloneable handle to a shared ADC
+#[derive(Clone)]
+pub struct AdcHandle(Shared<dyn Adc>);
+
+impl AdcHandle {
+    pub fn new<T: Adc + 'static>(adc: T) -> Self {
+        Self(Arc::new(Mutex::new(adc)))
+    }
+}
+
+impl Adc for AdcHandle {
+    fn read(&mut self, channel: u8) -> Result<u16, Error> {
+        lock(&self.0)?.read(channel)
+    }
+
+    fn max_value(&self) -> u16 {
+        lock(&self.0).map_or(0, |adc| adc.max_value())
+    }
+}
+
+/// Any of the handles above
+pub trait BusHandle: Clone + Send + Sync + 'static {}
+impl BusHandle for I2cHandle {}
+impl BusHandle for SpiHandle {}
+impl BusHandle for InputPinHandle {}
+impl BusHandle for OutputPinHandle {}
+impl BusHandle for SerialHandle {}
+impl BusHandle for OneWireHandle {}
+impl BusHandle for AdcHandle {}
+
+/// Every bus registered in the robot, indexed by kind and name,
+/// it also keeps track of the I2C addresses claimed by sensors
+#[derive(Resource, Default)]
+pub struct Buses {
+    buses: HashMap<(TypeId, String), Box<dyn Any + Send + Sync>>,
+    pub(crate) claims: shared::Claims,
+}
+
+impl Buses {
+    pub fn insert<H: BusHandle>(&mut self, name: &str, bus: H) {
+        self.buses
+            .insert((TypeId::of::<H>(), name.to_string()), Box::new(bus));
+    }
+
+    pub fn get<H: BusHandle>(&self, name: &str) -> Option<H> {
+        self.buses
+            .get(&(TypeId::of::<H>(), name.to_string()))
+            .and_then(|bus| bus.downcast_ref::<H>())
+            .cloned()
+    }
+}
+
+impl Robot {
+    /// Registers a bus so modules can request it by name
+    pub fn add_bus<H: BusHandle>(&mut self, name: &str, bus: H) {
+        self.world
+            .get_resource_or_insert_with(Buses::default)
+            .insert(name, bus);
+    }
+
+    pub fn with_bus<H: BusHandle>(mut self, name: &str, bus: H) -> Self {
+        self.add_bus(name, bus);
+        self
+    }
+
+    /// Gets a handle to a registered bus, usually called in Module::init
+    pub fn bus<H: BusHandle>(&self, name: &str) -> Option<H> {
+        self.world.get_resource::<Buses>()?.get(name)
+    }
+}
@@ -0,0 +1,173 @@
+use crate::error::Error;
+use crate::hal::{lock, Buses, I2cHandle};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use embedded_hal::i2c::{I2c, Operation};
+use std::collections::HashMap;
+
+/// Proxy to a single device on a shared I2C bus,
+/// every transaction holds the bus so systems never interleave their operations
+#[derive(Clone)]
+pub struct I2cDevice {
+    bus_name: String,
+    address: u8,
+    bus: I2cHandle,
+}
+
+impl I2cDevice {
+    pub fn new(bus_name: &str, address: u8, bus: I2cHandle) -> Self {
+        Self {
+            bus_name: bus_name.to_string(),
+            address,
+            bus,
+        }
+    }
+
+    pub fn bus_name(&self) -> &str {
+        &self.bus_name
+    }
+
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.bus.write(self.address, data)
+    }
+
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        self.bus.read(self.address, buffer)
+    }
+
+    pub fn write_read(&mut self, data: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        self.bus.write_read(self.address, data, buffer)
+    }
+
+    pub fn transaction(&mut self, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        self.bus.transaction(self.address, operations)
+    }
+
+    /// Holds the bus for a whole sequence of transactions,
+    /// used when a device needs a command and a delayed read without anyone in between
+    pub fn exclusive<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn I2c<Error = Error>, u8) -> Result<T, Error>,
+    {
+        let mut bus = lock(&self.bus.0)?;
+        f(&mut *bus, self.address)
+    }
+}
+
+/// Addresses claimed by sensors on each I2C bus
+#[derive(Default, Debug)]
+pub(crate) struct Claims {
+    addresses: HashMap<(String, u8), Entity>,
+}
+
+impl Claims {
+    /// Fails if another entity already owns the address on that bus
+    pub(crate) fn claim(&mut self, device: &I2cDevice, owner: Entity) -> Result<(), Error> {
+        let key = (device.bus_name.clone(), device.address);
+        match self.addresses.get(&key) {
+            Some(other) if *other != owner => Err(Error::AddressInUse(device.address)),
+            _ => {
+                self.addresses.insert(key, owner);
+                Ok(())
+            }
+        }
+    }
+
+    /// Frees every address owned by the entity
+    pub(crate) fn release(&mut self, owner: Entity) {
+        self.addresses.retain(|_, entity| *entity != owner);
+    }
+
+    pub(crate) fn owner(&self, bus_name: &str, address: u8) -> Option<Entity> {
+        self.addresses
+            .get(&(bus_name.to_string(), address))
+            .copied()
+    }
+}
+
+impl Robot {
+    /// Creates a proxy to a device on a registered I2C bus,
+    /// the address is claimed once the sensor using it is built
+    pub fn i2c_device(&self, bus_name: &str, address: u8) -> Option<I2cDevice> {
+        let bus = self.bus::<I2cHandle>(bus_name)?;
+        Some(I2cDevice::new(bus_name, address, bus))
+    }
+
+    /// Entity that claimed an address on an I2C bus
+    pub fn i2c_owner(&self, bus_name: &str, address: u8) -> Option<Entity> {
+        self.world
+            .get_resource::<Buses>()?
+            .claims
+            .owner(bus_name, address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::mock::MockI2c;
+    use crate::prelude::*;
+
+    #[test]
+    fn devices_share_a_bus() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x44, &[0x01]).expect_write(0x45, &[0x02]);
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+
+        let mut first = robot.i2c_device("i2c-1", 0x44).unwrap();
+        let mut second = robot.i2c_device("i2c-1", 0x45).unwrap();
+        let a = SensorBuilder::new("A", &mut robot)
+            .with_device(&first)
+            .build();
+        let b = SensorBuilder::new("B", &mut robot)
+            .with_device(&second)
+            .build();
+
+        first.write(&[0x01]).unwrap();
+        second.write(&[0x02]).unwrap();
+
+        mock.done();
+        assert_eq!(robot.i2c_owner("i2c-1", 0x44), Some(a));
+        assert_eq!(robot.i2c_owner("i2c-1", 0x45), Some(b));
+    }
+
+    #[test]
+    fn detects_address_collisions() {
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(MockI2c::new()));
+        let device = robot.i2c_device("i2c-1", 0x44).unwrap();
+
+        let first = SensorBuilder::new("A", &mut robot)
+            .with_device(&device)
+            .build();
+        let second = SensorBuilder::new("B", &mut robot)
+            .with_device(&device)
+            .try_build();
+
+        assert_eq!(second, Err(Error::AddressInUse(0x44)));
+        assert_eq!(robot.i2c_owner("i2c-1", 0x44), Some(first));
+    }
+
+    #[test]
+    fn exclusive_sequences_hold_the_bus() {
+        let mock = MockI2c::new();
+        mock.expect_write(0x44, &[0x24]).expect_read(0x44, &[0xAA]);
+        let robot = Robot::new().with_bus("i2c-1", I2cHandle::new(mock.clone()));
+        let mut device = robot.i2c_device("i2c-1", 0x44).unwrap();
+
+        let read = device
+            .exclusive(|bus, address| {
+                bus.write(address, &[0x24])?;
+                let mut buffer = [0];
+                bus.read(address, &mut buffer)?;
+                Ok(buffer[0])
+            })
+            .unwrap();
+
+        assert_eq!(read, 0xAA);
+        mock.done();
+    }
+}
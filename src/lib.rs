@@ -1,82 +1,296 @@
+pub mod drivers;
+pub mod error;
+pub mod hal;
 pub mod modules;
+pub mod runner;
 
 #[cfg(test)]
 mod test;
 
 // Used for building modules
 pub mod dev {
-    pub use crate::modules::{output::OutputBuilder, sensor::SensorBuilder, Descriptor, Module};
+    pub use crate::hal::{
+        Adc, AdcHandle, BusHandle, I2cDevice, I2cHandle, InputPinHandle, OneWire, OneWireHandle,
+        OutputPinHandle, Serial, SerialHandle, SpiHandle,
+    };
+    pub use crate::modules::{
+        actuator::ActuatorBuilder, lifecycle::Hooks, output::OutputBuilder, sensor::SensorBuilder,
+        Descriptor, Descriptors, Module,
+    };
     pub use crate::prelude::*;
 }
 
 // Used for normal users
 pub mod prelude {
+    pub use crate::error::Error;
     pub use crate::modules::{
-        output::{Output, OutputType, Reading},
-        sensor::{Features, SensorBuilder},
-        timer::{Duration, Timer},
-        Metadata,
+        actuator::{ActuatorBuilder, ActuatorType, Command, SafeSetpoint, Setpoint},
+        clock::{Clock, MockClock, RealClock, Time},
+        fault::{Faults, SensorFault},
+        handle::{OutputHandle, SensorHandle},
+        lifecycle::{
+            ActuatorAdded, ActuatorRemoved, ModuleCommands, ModuleId, SensorAdded, SensorRemoved,
+            ShutdownReport, ShutdownTimeout,
+        },
+        names::{SensorLookup, SensorNames},
+        output::{
+            MaxAge, Output, OutputKind, OutputType, OutputTypes, Reading, SensorOutputs, Status,
+        },
+        sensor::{Features, HierarchyError, SensorBuilder},
+        timer::{Duration, Timed, Timer, TimerMode, TimerReady},
+        unit::{Quantity, Unit},
+        Id, Metadata,
     };
-    pub use crate::{DefaultStage, Robot};
+    pub use crate::runner::{Limit, LoopReport, Shutdown, ShutdownFlag};
+    pub use crate::{Robot, RobotStage};
 }
 
 // TODO: To be able to pub use prelude i need to port the macro libs
 use bevy_ecs::prelude::*;
+use bevy_ecs::schedule::SystemDescriptor;
+use bevy_ecs::system::BoxedSystem;
+use std::any::TypeId;
+use std::collections::HashSet;
 
-use crate::modules::{Module, UndefinedType};
+use crate::modules::{
+    actuator::{command_system, park_system},
+    clock::{Clock, Time},
+    lifecycle::{
+        ActuatorAdded, ActuatorRemoved, ModuleCommands, ModuleHooks, ModuleId, SensorAdded,
+        SensorRemoved, ShutdownTimeout,
+    },
+    names::SensorNames,
+    output::{reading_system, OutputTypes},
+    Descriptors, Module, UndefinedType,
+};
+use crate::runner::Shutdown;
 
 // Before we build the framework
 
-// TODO: allow to specify multiple labels
+/// Built in stages, the ones in ALL run every pass in the order they are declared
+#[derive(StageLabel, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RobotStage {
+    /// Timers and anything that must be ready before sensors are read
+    Input,
+    /// Sensor reading systems
+    Read,
+    /// Default stage, used for logic that consumes the readings
+    Process,
+    /// Pending commands get applied and actuators are driven
+    Actuate,
+    /// Displays, loggers and anything that needs the final state of the run
+    Output,
+    /// Runs once before the first run, after the modules' on_start hooks
+    Startup,
+    /// Runs on Robot::shutdown, followed by Actuate so actuators can be parked
+    Shutdown,
+}
+
+/// Internal stage that runs after every RobotStage, used for bookkeeping
 #[derive(StageLabel)]
-pub struct DefaultStage;
+struct PostStage;
+
+impl RobotStage {
+    pub const ALL: [RobotStage; 5] = [
+        RobotStage::Input,
+        RobotStage::Read,
+        RobotStage::Process,
+        RobotStage::Actuate,
+        RobotStage::Output,
+    ];
+}
 
 /// Runtime
 pub struct Robot {
     world: World,
     scheduler: Schedule,
+    startup: Schedule,
+    shutdown: Schedule,
+    // Systems identified by their type, used to avoid installing them twice
+    systems: HashSet<(RobotStage, TypeId)>,
+    // Hooks of every module in the order they were added
+    modules: Vec<ModuleHooks>,
+    next_module: u64,
+    started: bool,
+}
+
+impl Default for Robot {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Robot {
     pub fn new() -> Self {
         let mut scheduler = Schedule::default();
-        scheduler.add_stage(DefaultStage, SystemStage::single_threaded());
+        for stage in RobotStage::ALL {
+            scheduler.add_stage(stage, SystemStage::single_threaded());
+        }
+        scheduler.add_stage(
+            PostStage,
+            SystemStage::single_threaded().with_system(reading_system),
+        );
+        let mut startup = Schedule::default();
+        startup.add_stage(RobotStage::Startup, SystemStage::single_threaded());
+        let mut shutdown = Schedule::default();
+        shutdown.add_stage(RobotStage::Shutdown, SystemStage::single_threaded());
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<OutputTypes>();
+        let mut descriptors = Descriptors::default();
+        for (_, kind) in world.resource::<OutputTypes>().iter() {
+            descriptors
+                .register(kind)
+                .expect("built in output types are unique");
+        }
+        world.insert_resource(descriptors);
+        world.init_resource::<ModuleCommands>();
+        world.init_resource::<SensorNames>();
+        world.init_resource::<ShutdownTimeout>();
+        world.init_resource::<Shutdown>();
+        world.init_resource::<Events<SensorAdded>>();
+        world.init_resource::<Events<SensorRemoved>>();
+        world.init_resource::<Events<ActuatorAdded>>();
+        world.init_resource::<Events<ActuatorRemoved>>();
 
-        Self {
-            world: World::new(),
+        let mut robot = Self {
+            world,
             scheduler,
-        }
+            startup,
+            shutdown,
+            systems: HashSet::new(),
+            modules: vec![],
+            next_module: 0,
+            started: false,
+        };
+        robot.add_system_to_stage(RobotStage::Actuate, command_system);
+        robot.add_system_to_stage(RobotStage::Shutdown, park_system);
+        robot
     }
 
+    /// Runs a single pass through every stage, the first pass starts the robot
+    /// and modules queued in ModuleCommands are added or removed before each pass
     pub fn run(&mut self) {
+        self.start();
+        self.apply_module_commands();
+        self.update_lifecycle_events();
+
+        self.world.resource_mut::<Time>().update();
         self.scheduler.run(&mut self.world);
     }
 
+    /// Replaces the clock used by the robot's Time
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    pub fn set_clock<C: Clock>(&mut self, clock: C) {
+        self.world.insert_resource(Time::new(clock));
+    }
+
+    /// Adds or replaces a resource systems can read with Res
+    pub fn with_resource<R: Resource>(mut self, resource: R) -> Self {
+        self.insert_resource(resource);
+        self
+    }
+
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) {
+        self.world.insert_resource(resource);
+    }
+
+    pub fn resource<R: Resource>(&self) -> Option<&R> {
+        self.world.get_resource::<R>()
+    }
+
+    pub fn resource_mut<R: Resource>(&mut self) -> Option<Mut<'_, R>> {
+        self.world.get_resource_mut::<R>()
+    }
+
     /// Adds a module, this only returns Self to follow the builder pattern
-    pub fn with<T: Module<E>, E>(mut self, module: T) -> Self {
+    pub fn with<T: Module<E> + 'static, E>(mut self, module: T) -> Self {
         self.add(module);
         self
     }
 
     /// Adds a module while also returning the module's initialization response
-    pub fn add<T: Module<E>, E>(&mut self, module: T) -> E {
-        module.init(self)
+    pub fn add<T: Module<E> + 'static, E>(&mut self, module: T) -> E {
+        self.add_with_id(module).1
     }
 
+    /// Same as add but also returns the id remove_module needs to take the module out again
+    pub fn add_with_id<T: Module<E> + 'static, E>(&mut self, module: T) -> (ModuleId, E) {
+        let initialized = module.init(self);
+        let id = self.register_hooks::<T, E>(&initialized);
+        (id, initialized)
+    }
+
+    /// Adds a system to the Process stage
     pub fn add_system<F, Params>(&mut self, system: F)
     where
-        F: IntoSystemDescriptor<Params>,
+        F: IntoSystemDescriptor<Params> + 'static,
     {
-        self.scheduler.add_system_to_stage(DefaultStage, system);
+        self.add_system_to_stage(RobotStage::Process, system);
     }
 
     pub fn with_system<F, Params>(mut self, system: F) -> Self
     where
-        F: IntoSystemDescriptor<Params>,
+        F: IntoSystemDescriptor<Params> + 'static,
     {
         self.add_system(system);
         self
     }
+
+    /// Adds a system to a specific stage, systems are identified by their type
+    /// so adding the same system to the same stage again is ignored.
+    /// A SystemDescriptor, like a system with labels or run criteria, is never deduplicated
+    pub fn add_system_to_stage<F, Params>(&mut self, stage: RobotStage, system: F)
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.add_system_as::<F, _, _>(stage, system);
+    }
+
+    pub fn with_system_in<F, Params>(mut self, stage: RobotStage, system: F) -> Self
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.add_system_to_stage(stage, system);
+        self
+    }
+
+    /// Installs a system under the identity of F,
+    /// used when a system is wrapped in a descriptor before being added.
+    /// Descriptors and boxed systems can't be told apart so they are always installed
+    pub(crate) fn add_system_as<F, S, Params>(&mut self, stage: RobotStage, system: S)
+    where
+        F: 'static,
+        S: IntoSystemDescriptor<Params>,
+    {
+        let id = TypeId::of::<F>();
+        let anonymous =
+            id == TypeId::of::<SystemDescriptor>() || id == TypeId::of::<BoxedSystem<(), ()>>();
+
+        if anonymous || self.systems.insert((stage, id)) {
+            let schedule = match stage {
+                RobotStage::Startup => &mut self.startup,
+                RobotStage::Shutdown => &mut self.shutdown,
+                _ => &mut self.scheduler,
+            };
+            schedule.add_system_to_stage(stage, system);
+        }
+    }
+
+    /// Checks if a system is installed in any stage
+    pub fn has_system<F: 'static>(&self, _system: &F) -> bool {
+        let id = TypeId::of::<F>();
+        self.systems.iter().any(|(_, system)| *system == id)
+    }
+
+    /// Checks if a system is installed in the given stage
+    pub fn has_system_in<F: 'static>(&self, stage: RobotStage, _system: &F) -> bool {
+        self.systems.contains(&(stage, TypeId::of::<F>()))
+    }
 }
 
 #[cfg(test)]
@@ -86,25 +300,26 @@ mod tests {
 
     fn simulate_readings(mut query: Query<&mut Reading>) {
         for mut reading in &mut query {
-            reading.0 += 1.0;
+            let n = reading.value() + 1.0;
+            reading.set(n);
         }
     }
 
     fn print_readings(query: Query<(&Output, &Reading)>) {
         for (output, reading) in &query {
-            println!("{:?} read {:?}", output.0, reading.0);
+            println!("{:?} read {:?}", output.0, reading.value());
         }
     }
 
     fn init() {
         let mut robot = Robot::new()
-            .with_system(simulate_readings)
-            .with_system(print_readings);
+            .with_system_in(RobotStage::Read, simulate_readings)
+            .with_system_in(RobotStage::Output, print_readings);
 
         SensorBuilder::new("SHT31", &mut robot)
             .with_type(&UndefinedType)
-            .with_output(OutputType::Humidity)
-            .with_output(OutputType::Temperature)
+            .with_output(OutputType::HUMIDITY)
+            .with_output(OutputType::TEMPERATURE)
             .build();
 
         for _ in 0..10 {
@@ -116,4 +331,84 @@ mod tests {
     fn it_works() {
         init()
     }
+
+    #[test]
+    fn systems_are_installed_once() {
+        let mut robot = Robot::new();
+
+        let sensors: Vec<Entity> = (0..2)
+            .map(|i| {
+                SensorBuilder::new(&format!("Sensor {}", i), &mut robot)
+                    .with_output(OutputType::MOISTURE)
+                    .with_system(simulate_readings)
+                    .build()
+            })
+            .collect();
+
+        assert!(robot.has_system(&simulate_readings));
+        assert!(robot.has_system_in(RobotStage::Read, &simulate_readings));
+        assert!(!robot.has_system_in(RobotStage::Process, &simulate_readings));
+
+        for _ in 0..3 {
+            robot.run();
+        }
+
+        for sensor in sensors {
+            let output = robot.world.get::<Features>(sensor).unwrap().0[0];
+            let reading = robot.world.get::<Reading>(output).unwrap();
+            assert_eq!(reading.value(), 3.0);
+            assert_eq!(reading.sequence(), 3);
+        }
+    }
+
+    fn pump_command(mut query: Query<&mut Command>) {
+        for mut command in &mut query {
+            command.set(0.5);
+        }
+    }
+
+    #[derive(Resource)]
+    struct Gain(f64);
+
+    fn apply_gain(gain: Res<Gain>, mut query: Query<&mut Reading>) {
+        for mut reading in &mut query {
+            let n = reading.value() * gain.0;
+            reading.set(n);
+        }
+    }
+
+    #[test]
+    fn resources_are_shared_with_systems() {
+        let mut robot = Robot::new()
+            .with_resource(Gain(2.0))
+            .with_system(apply_gain);
+        let sensor = SensorBuilder::new("Sensor", &mut robot)
+            .with_output(OutputType::MOISTURE)
+            .build();
+        let output = robot.readings_of(sensor).unwrap()[0].0;
+
+        robot.set_reading(output, 1.0).unwrap();
+        robot.run();
+        assert_eq!(robot.reading(output).unwrap().value(), 2.0);
+
+        robot.resource_mut::<Gain>().unwrap().0 = 3.0;
+        robot.run();
+        assert_eq!(robot.reading(output).unwrap().value(), 6.0);
+        assert_eq!(robot.resource::<Gain>().unwrap().0, 3.0);
+        assert_eq!(robot.metadata(sensor).unwrap().name, "Undefined");
+    }
+
+    #[test]
+    fn actuator_applies_commands() {
+        let mut robot = Robot::new().with_system(pump_command);
+
+        let pump = ActuatorBuilder::new("Pump", &mut robot)
+            .with_type(&ActuatorType::Pump)
+            .build();
+
+        robot.run();
+
+        assert_eq!(robot.world.get::<Setpoint>(pump).unwrap().0, 0.5);
+        assert!(!robot.world.get::<Command>(pump).unwrap().is_pending());
+    }
 }
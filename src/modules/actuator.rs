@@ -0,0 +1,241 @@
+use crate::error::Error;
+use crate::modules::lifecycle::{ActuatorAdded, ActuatorRemoved};
+use crate::modules::{sensor::Name, Descriptor, Descriptors, Id, Metadata, UndefinedType};
+use crate::{Robot, RobotStage};
+use bevy_ecs::prelude::*;
+use std::any::TypeId;
+use std::ops::Deref;
+
+/// Actuator setup helper
+/// Actuators are the writable counterpart of sensors, instead of producing readings
+/// they hold a pending Command which gets applied into their Setpoint every run
+pub struct ActuatorBuilder<'c> {
+    robot: &'c mut Robot,
+    name: String,
+    metadata: Metadata,
+    // Type that provided the metadata, its id gets claimed on build
+    descriptor: Option<(TypeId, &'static str)>,
+    setpoint: f64,
+    safe_setpoint: Option<f64>,
+    entity: Entity,
+}
+
+impl<'c> ActuatorBuilder<'c> {
+    /// Creates an undefined actuator with a setpoint of 0
+    pub fn new(name: &str, robot: &'c mut Robot) -> Self {
+        let entity = robot.world.spawn(()).id();
+
+        Self {
+            name: name.into(),
+            robot,
+            metadata: UndefinedType.metadata(),
+            descriptor: None,
+            setpoint: 0.0,
+            safe_setpoint: None,
+            entity,
+        }
+    }
+
+    /// Sets the actuators type, which contains helpful metadata for loggers
+    pub fn with_type<T: Descriptor + 'static>(mut self, actuator_type: &T) -> Self {
+        self.set_type(actuator_type);
+        self
+    }
+
+    pub fn set_type<T: Descriptor + 'static>(&mut self, actuator_type: &T) {
+        self.metadata = actuator_type.metadata();
+        self.descriptor = Some((TypeId::of::<T>(), std::any::type_name::<T>()));
+    }
+
+    /// Sets the value the actuator starts at
+    pub fn with_setpoint(mut self, setpoint: f64) -> Self {
+        self.set_setpoint(setpoint);
+        self
+    }
+
+    pub fn set_setpoint(&mut self, setpoint: f64) {
+        self.setpoint = setpoint;
+    }
+
+    /// Sets the value the actuator is parked at when the robot shuts down
+    pub fn with_safe_setpoint(mut self, setpoint: f64) -> Self {
+        self.set_safe_setpoint(setpoint);
+        self
+    }
+
+    pub fn set_safe_setpoint(&mut self, setpoint: f64) {
+        self.safe_setpoint = Some(setpoint);
+    }
+
+    /// Registers the system that drives the hardware,
+    /// it always runs after the pending commands have been applied
+    pub fn with_system<F, Params>(mut self, system: F) -> Self
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.set_system(system);
+        self
+    }
+
+    pub fn set_system<F, Params>(&mut self, system: F)
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.robot
+            .add_system_as::<F, _, _>(RobotStage::Actuate, system.after(command_system));
+    }
+
+    pub fn add_component<T: Component>(&mut self, component: T) {
+        self.robot
+            .world
+            .get_entity_mut(self.entity)
+            .unwrap()
+            .insert(component);
+    }
+
+    pub fn with_component<T: Component>(mut self, component: T) -> Self {
+        self.add_component(component);
+        self
+    }
+
+    /// Handles setting up all the necessary actuator components,
+    /// panics if its type's id is used by another type
+    pub fn build(self) -> Entity {
+        let name = self.name.clone();
+        let id = self.metadata.id.clone();
+        self.try_build()
+            .unwrap_or_else(|e| panic!("failed to build actuator {} ({}): {}", name, id, e))
+    }
+
+    /// Same as build but returns an error instead of panicking
+    pub fn try_build(self) -> Result<Entity, Error> {
+        if let Some((type_id, type_name)) = self.descriptor {
+            let claimed = self
+                .robot
+                .world
+                .get_resource_or_insert_with(Descriptors::default)
+                .claim(self.metadata.id.clone(), type_id, type_name);
+            if let Err(e) = claimed {
+                self.robot.world.despawn(self.entity);
+                return Err(e);
+            }
+        }
+
+        let mut ent = self.robot.world.get_entity_mut(self.entity).unwrap();
+        ent.insert(ActuatorBundle {
+            name: Name(self.name),
+            meta: self.metadata,
+            command: Command::default(),
+            setpoint: Setpoint(self.setpoint),
+        });
+        if let Some(setpoint) = self.safe_setpoint {
+            ent.insert(SafeSetpoint(setpoint));
+        }
+        self.robot.world.send_event(ActuatorAdded(self.entity));
+
+        Ok(self.entity)
+    }
+}
+
+#[derive(Bundle, Debug)]
+pub struct ActuatorBundle {
+    name: Name,
+    meta: Metadata,
+    command: Command,
+    setpoint: Setpoint,
+}
+
+#[derive(Component, Default, Debug)]
+/// Pending value that will be written into the actuator on the next run
+pub struct Command(pub Option<f64>);
+impl Command {
+    pub fn set(&mut self, n: f64) {
+        self.0 = Some(n);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+#[derive(Component, Default, Debug)]
+/// Value the actuator is currently being driven at
+pub struct Setpoint(pub f64);
+impl Deref for Setpoint {
+    type Target = f64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Component, Debug)]
+/// Value the actuator is parked at when the robot shuts down
+pub struct SafeSetpoint(pub f64);
+
+#[derive(Component, Default, Debug)]
+/// Actuator metadata descriptor
+pub enum ActuatorType {
+    #[default]
+    Relay,
+    Pump,
+    Motor,
+    Servo,
+}
+
+impl Descriptor for ActuatorType {
+    fn id(&self) -> Id {
+        match self {
+            ActuatorType::Relay => Id::new("robotrs", "actuator", "relay"),
+            ActuatorType::Pump => Id::new("robotrs", "actuator", "pump"),
+            ActuatorType::Motor => Id::new("robotrs", "actuator", "motor"),
+            ActuatorType::Servo => Id::new("robotrs", "actuator", "servo"),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ActuatorType::Relay => "Relay".to_string(),
+            ActuatorType::Pump => "Pump".to_string(),
+            ActuatorType::Motor => "Motor".to_string(),
+            ActuatorType::Servo => "Servo".to_string(),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            ActuatorType::Relay => "On/off switch".to_string(),
+            ActuatorType::Pump => "Fluid pump driven by duty cycle".to_string(),
+            ActuatorType::Motor => "Motor driven by speed".to_string(),
+            ActuatorType::Servo => "Servo driven by angle".to_string(),
+        }
+    }
+}
+
+/// Moves every pending command into its actuator's setpoint
+pub(crate) fn command_system(mut query: Query<(&mut Command, &mut Setpoint)>) {
+    for (mut command, mut setpoint) in &mut query {
+        if let Some(n) = command.0.take() {
+            setpoint.0 = n;
+        }
+    }
+}
+
+/// Commands every actuator with a SafeSetpoint to it, runs in the Shutdown stage
+pub(crate) fn park_system(mut query: Query<(&SafeSetpoint, &mut Command)>) {
+    for (safe, mut command) in &mut query {
+        command.set(safe.0);
+    }
+}
+
+impl Robot {
+    /// Despawns an actuator with all of its components, it isn't parked first
+    pub fn remove_actuator(&mut self, actuator: Entity) -> Result<(), Error> {
+        if self.world.get::<Setpoint>(actuator).is_none() {
+            return Err(Error::NotAnActuator);
+        }
+        self.world.despawn(actuator);
+        self.world.send_event(ActuatorRemoved(actuator));
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use bevy_ecs::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Source of time for the robot
+pub trait Clock: Send + Sync + 'static {
+    /// Time elapsed since the clock started, it must never go backwards
+    fn now(&self) -> std::time::Duration;
+
+    /// Blocks until the duration has passed on this clock, used by the run loops
+    fn sleep(&self, duration: std::time::Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Monotonic clock backed by the system's Instant
+#[derive(Debug)]
+pub struct RealClock {
+    start: Instant,
+}
+
+impl RealClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> std::time::Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Manually advanced clock, clones share the same time
+/// so a test can keep a copy and step the one inside the Robot
+#[derive(Clone, Default, Debug)]
+pub struct MockClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    pub fn set(&self, now: std::time::Duration) {
+        self.nanos.store(now.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> std::time::Duration {
+        std::time::Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+
+    /// Returns right away after advancing the clock,
+    /// so run loops go through their ticks without waiting
+    fn sleep(&self, duration: std::time::Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Robot's time, sampled once at the start of every run
+/// so every system in that run sees the same value
+#[derive(Resource)]
+pub struct Time {
+    clock: Box<dyn Clock>,
+    now: std::time::Duration,
+    delta: std::time::Duration,
+}
+
+impl Time {
+    pub fn new<C: Clock>(clock: C) -> Self {
+        let now = clock.now();
+        Self {
+            clock: Box::new(clock),
+            now,
+            delta: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Time of the current run
+    pub fn now(&self) -> std::time::Duration {
+        self.now
+    }
+
+    /// Time between the current and the previous run
+    pub fn delta(&self) -> std::time::Duration {
+        self.delta
+    }
+
+    /// Clock the time is sampled from
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    pub(crate) fn update(&mut self) {
+        // Guard against clocks that don't uphold the monotonic requirement
+        let now = self.clock.now().max(self.now);
+        self.delta = now - self.now;
+        self.now = now;
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new(RealClock::new())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::error::Error;
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+
+#[derive(Component, Clone, PartialEq, Debug)]
+/// Attached to a sensor whose last read failed
+pub struct SensorFault {
+    /// Latest error
+    pub error: Error,
+    /// Amount of consecutive failed reads
+    pub count: u32,
+}
+
+impl SensorFault {
+    pub fn new(error: Error) -> Self {
+        Self { error, count: 1 }
+    }
+}
+
+/// Lets reading systems report and clear their sensor's faults
+#[derive(SystemParam)]
+pub struct Faults<'w, 's> {
+    commands: Commands<'w, 's>,
+    faults: Query<'w, 's, &'static mut SensorFault>,
+}
+
+impl<'w, 's> Faults<'w, 's> {
+    /// Attaches a fault to the sensor or updates the existing one
+    pub fn report(&mut self, sensor: Entity, error: Error) {
+        match self.faults.get_mut(sensor) {
+            Ok(mut fault) => {
+                fault.error = error;
+                fault.count += 1;
+            }
+            Err(_) => {
+                self.commands.entity(sensor).insert(SensorFault::new(error));
+            }
+        }
+    }
+
+    /// Removes the sensor's fault if there is one
+    pub fn clear(&mut self, sensor: Entity) {
+        if self.faults.contains(sensor) {
+            self.commands.entity(sensor).remove::<SensorFault>();
+        }
+    }
+}
+
+impl Robot {
+    /// Lists every faulty sensor
+    pub fn faults(&mut self) -> Vec<(Entity, SensorFault)> {
+        self.world
+            .query::<(Entity, &SensorFault)>()
+            .iter(&self.world)
+            .map(|(entity, fault)| (entity, fault.clone()))
+            .collect()
+    }
+
+    /// Removes a sensor's fault, returning it if there was one
+    pub fn clear_fault(&mut self, sensor: Entity) -> Option<SensorFault> {
+        self.world.get_entity_mut(sensor)?.remove::<SensorFault>()
+    }
+
+    pub fn clear_faults(&mut self) {
+        for (sensor, _) in self.faults() {
+            self.clear_fault(sensor);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Typed references to built sensors and their outputs
+
+use crate::error::Error;
+use crate::modules::output::{OutputType, Reading};
+use crate::modules::sensor::{Features, Name};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// Reference to a single output of a sensor
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OutputHandle {
+    entity: Entity,
+    sensor: Entity,
+    kind: OutputType,
+}
+
+impl OutputHandle {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Sensor the output belongs to
+    pub fn sensor(&self) -> Entity {
+        self.sensor
+    }
+
+    pub fn kind(&self) -> OutputType {
+        self.kind
+    }
+}
+
+impl Deref for OutputHandle {
+    type Target = Entity;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entity
+    }
+}
+
+/// Reference to a sensor built around the component T,
+/// returned by modules so users can't mix up sensors of different types
+pub struct SensorHandle<T> {
+    entity: Entity,
+    name: String,
+    outputs: Vec<OutputHandle>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SensorHandle<T> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Every output in the order they were registered
+    pub fn outputs(&self) -> &[OutputHandle] {
+        &self.outputs
+    }
+
+    /// First output of the given type
+    pub fn output(&self, kind: OutputType) -> Option<OutputHandle> {
+        self.outputs_of(kind).next()
+    }
+
+    /// Every output of the given type, for sensors with one output per axis or channel
+    pub fn outputs_of(&self, kind: OutputType) -> impl Iterator<Item = OutputHandle> + '_ {
+        self.outputs.iter().copied().filter(move |o| o.kind == kind)
+    }
+}
+
+impl<T> Deref for SensorHandle<T> {
+    type Target = Entity;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entity
+    }
+}
+
+// Implemented by hand so T doesn't need to be Clone or Debug
+impl<T> Clone for SensorHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            entity: self.entity,
+            name: self.name.clone(),
+            outputs: self.outputs.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for SensorHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entity == other.entity
+    }
+}
+
+impl<T> fmt::Debug for SensorHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SensorHandle")
+            .field("type", &std::any::type_name::<T>())
+            .field("entity", &self.entity)
+            .field("name", &self.name)
+            .field("outputs", &self.outputs)
+            .finish()
+    }
+}
+
+impl Robot {
+    /// Creates a handle for an existing sensor, fails if the entity isn't a sensor,
+    /// one of its outputs is gone or it has no T component
+    pub fn sensor_handle<T: Component>(&self, sensor: Entity) -> Result<SensorHandle<T>, Error> {
+        let entity = self.world.get_entity(sensor).ok_or(Error::NotASensor)?;
+        let features = entity.get::<Features>().ok_or(Error::NotASensor)?;
+        if !entity.contains::<T>() {
+            return Err(Error::MissingComponent(std::any::type_name::<T>()));
+        }
+
+        let outputs = features
+            .iter()
+            .map(|output| {
+                let kind = *self
+                    .world
+                    .get::<OutputType>(*output)
+                    .ok_or(Error::NotAnOutput)?;
+                Ok(OutputHandle {
+                    entity: *output,
+                    sensor,
+                    kind,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(SensorHandle {
+            entity: sensor,
+            name: entity
+                .get::<Name>()
+                .map(|n| n.0.clone())
+                .unwrap_or_default(),
+            outputs,
+            marker: PhantomData,
+        })
+    }
+
+    /// Component the sensor was built around, None once the sensor is removed
+    pub fn sensor<T: Component>(&self, handle: &SensorHandle<T>) -> Option<&T> {
+        self.world.get::<T>(handle.entity)
+    }
+
+    pub fn sensor_mut<T: Component>(&mut self, handle: &SensorHandle<T>) -> Option<Mut<'_, T>> {
+        self.world.get_mut::<T>(handle.entity)
+    }
+
+    /// Latest reading of the output, it carries the unit it's stored in
+    pub fn output_reading(&self, handle: &OutputHandle) -> Option<&Reading> {
+        self.world.get::<Reading>(handle.entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use bevy_ecs::prelude::*;
+
+    #[derive(Component)]
+    struct Probe(u8);
+
+    #[derive(Component)]
+    struct Other;
+
+    #[test]
+    fn handles_are_typed() {
+        let mut robot = Robot::new();
+        let handle = SensorBuilder::new("Probe", &mut robot)
+            .with_output(OutputType::TEMPERATURE)
+            .with_output(OutputType::HUMIDITY)
+            .with_component(Probe(3))
+            .build_handle::<Probe>();
+
+        assert_eq!(handle.name(), "Probe");
+        assert_eq!(robot.sensor(&handle).unwrap().0, 3);
+        assert_eq!(
+            robot.sensor_handle::<Other>(handle.entity()),
+            Err(Error::MissingComponent(std::any::type_name::<Other>()))
+        );
+
+        let humidity = handle.output(OutputType::HUMIDITY).unwrap();
+        assert_eq!(humidity.sensor(), handle.entity());
+        assert_eq!(handle.outputs()[1], humidity);
+        assert!(handle.output(OutputType::PRESSURE).is_none());
+
+        let reading = robot.output_reading(&humidity).unwrap();
+        assert_eq!(reading.sequence(), 0);
+        assert_eq!(reading.unit(), Unit::RelativeHumidity);
+
+        robot.remove_sensor(handle.entity()).unwrap();
+        assert!(robot.sensor(&handle).is_none());
+        assert!(robot.output_reading(&humidity).is_none());
+        assert_eq!(
+            robot.sensor_handle::<Probe>(handle.entity()),
+            Err(Error::NotASensor)
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! Starting and stopping the robot, and adding and removing modules while it runs
+
+use crate::error::Error;
+use crate::modules::{timer::Duration, Module};
+use crate::{Robot, RobotStage};
+use bevy_ecs::prelude::*;
+use std::sync::mpsc::{self, RecvTimeoutError};
+
+/// Sent once a sensor has been built
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SensorAdded(pub Entity);
+
+/// Sent once a sensor and its outputs have been despawned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SensorRemoved(pub Entity);
+
+/// Sent once an actuator has been built
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ActuatorAdded(pub Entity);
+
+/// Sent once an actuator has been despawned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ActuatorRemoved(pub Entity);
+
+type Deferred = Box<dyn FnOnce(&mut Robot) + Send + Sync>;
+
+/// Queue systems use to add and remove modules, changes are applied
+/// between ticks so no system ever sees a half built sensor
+#[derive(Resource, Default)]
+pub struct ModuleCommands {
+    queue: Vec<Deferred>,
+}
+
+impl ModuleCommands {
+    /// Queues any change to the robot
+    pub fn push<F: FnOnce(&mut Robot) + Send + Sync + 'static>(&mut self, f: F) {
+        self.queue.push(Box::new(f));
+    }
+
+    /// Initializes the module before the next tick
+    pub fn add<M, T>(&mut self, module: M)
+    where
+        M: Module<T> + Send + Sync + 'static,
+        T: 'static,
+    {
+        self.push(move |robot| {
+            robot.add(module);
+        });
+    }
+
+    /// Initializes the module before the next tick and hands its id
+    /// and what it returned to `then`
+    pub fn add_then<M, T, F>(&mut self, module: M, then: F)
+    where
+        M: Module<T> + Send + Sync + 'static,
+        T: 'static,
+        F: FnOnce(&mut Robot, ModuleId, T) + Send + Sync + 'static,
+    {
+        self.push(move |robot| {
+            let (id, initialized) = robot.add_with_id(module);
+            then(robot, id, initialized);
+        });
+    }
+
+    /// Tears the module down before the next tick, see Robot::remove_module
+    pub fn remove<M, T>(&mut self, id: ModuleId, initialized: T)
+    where
+        M: Module<T> + 'static,
+        T: Send + Sync + 'static,
+    {
+        self.push(move |robot| robot.remove_module::<M, T>(id, initialized));
+    }
+
+    /// Removes a sensor before the next tick, see Robot::remove_sensor
+    pub fn remove_sensor(&mut self, sensor: Entity) {
+        self.push(move |robot| {
+            // It may have been removed by an earlier command
+            let _ = robot.remove_sensor(sensor);
+        });
+    }
+
+    /// Removes an actuator before the next tick, see Robot::remove_actuator
+    pub fn remove_actuator(&mut self, actuator: Entity) {
+        self.push(move |robot| {
+            // It may have been removed by an earlier command
+            let _ = robot.remove_actuator(actuator);
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+type StartHook = Box<dyn FnMut(&mut Robot) + Send + Sync>;
+type ShutdownHook = Box<dyn FnMut(&mut Robot) -> Result<(), Error> + Send + Sync>;
+
+/// Start and shutdown hooks of one module, see Module::hooks
+#[derive(Default)]
+pub struct Hooks {
+    on_start: Option<StartHook>,
+    on_shutdown: Option<ShutdownHook>,
+}
+
+impl Hooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs before the Startup stage, used for one time device configuration
+    pub fn on_start<F: FnMut(&mut Robot) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+
+    /// Runs on Robot::shutdown after the Shutdown stage,
+    /// fails if the hardware couldn't be put in a safe state
+    pub fn on_shutdown<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&mut Robot) -> Result<(), Error> + Send + Sync + 'static,
+    {
+        self.on_shutdown = Some(Box::new(f));
+        self
+    }
+}
+
+/// Identifies one added module, used to remove it again
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ModuleId(u64);
+
+/// Hooks of a module that was added, named after its type for the ShutdownReport
+pub(crate) struct ModuleHooks {
+    id: ModuleId,
+    name: &'static str,
+    hooks: Hooks,
+}
+
+/// How long each module's on_shutdown gets to put its hardware in a safe state.
+/// Hooks can't be interrupted, so one still running once its time is up is taken
+/// as stuck on the hardware and the process exits with a failure status
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ShutdownTimeout(pub std::time::Duration);
+
+impl Default for ShutdownTimeout {
+    fn default() -> Self {
+        Self(std::time::Duration::from_secs(1))
+    }
+}
+
+/// Exits the process unless it's disarmed before the timeout
+struct Watchdog(mpsc::Sender<()>);
+
+impl Watchdog {
+    fn arm(module: &'static str, timeout: std::time::Duration) -> Self {
+        let (done, finished) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+                eprintln!("{} didn't shut down within {:?}", module, timeout);
+                std::process::exit(1);
+            }
+        });
+        Self(done)
+    }
+
+    fn disarm(self) {
+        // The thread may already be gone if the hook returned right at the timeout
+        let _ = self.0.send(());
+    }
+}
+
+/// Outcome of Robot::shutdown
+#[derive(Default, Debug)]
+pub struct ShutdownReport {
+    /// Modules whose on_shutdown failed, by type name
+    pub failures: Vec<(&'static str, Error)>,
+}
+
+impl ShutdownReport {
+    /// True when every module shut down cleanly
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Exit status for the process, failure if any module didn't shut down cleanly
+    pub fn exit_code(&self) -> std::process::ExitCode {
+        if self.is_clean() {
+            std::process::ExitCode::SUCCESS
+        } else {
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+impl Robot {
+    /// Runs the modules' on_start hooks in the order they were added, then the Startup stage.
+    /// The first run calls this, later calls do nothing until the robot is shut down
+    pub fn start(&mut self) {
+        if self.started {
+            return;
+        }
+
+        // Hooks can add modules, those get started as well
+        let mut i = 0;
+        while i < self.modules.len() {
+            self.run_start_hook(i);
+            i += 1;
+        }
+        self.started = true;
+        self.startup.run(&mut self.world);
+    }
+
+    /// Runs the Shutdown stage then Actuate so actuators get driven to their SafeSetpoint,
+    /// then the modules' on_shutdown hooks in the order they were added.
+    /// The next run starts the robot again
+    pub fn shutdown(&mut self) -> ShutdownReport {
+        self.shutdown.run(&mut self.world);
+        if let Some(stage) = self
+            .scheduler
+            .get_stage_mut::<SystemStage>(RobotStage::Actuate)
+        {
+            stage.run(&mut self.world);
+        }
+
+        let timeout = self.world.resource::<ShutdownTimeout>().0;
+        let mut report = ShutdownReport::default();
+        for i in 0..self.modules.len() {
+            // Taken out while it runs so the hook can borrow the robot
+            let Some(mut on_shutdown) = self.modules[i].hooks.on_shutdown.take() else {
+                continue;
+            };
+            let watchdog = Watchdog::arm(self.modules[i].name, timeout);
+            let result = on_shutdown(self);
+            watchdog.disarm();
+            self.modules[i].hooks.on_shutdown = Some(on_shutdown);
+            if let Err(e) = result {
+                report.failures.push((self.modules[i].name, e));
+            }
+        }
+        self.started = false;
+        report
+    }
+
+    /// Sets how long each module gets to shut down, see ShutdownTimeout
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.set_shutdown_timeout(timeout);
+        self
+    }
+
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.world.insert_resource(ShutdownTimeout(timeout.into()));
+    }
+
+    /// Keeps track of a module's hooks, a robot that's already running starts it right away
+    pub(crate) fn register_hooks<M: Module<T> + 'static, T>(
+        &mut self,
+        initialized: &T,
+    ) -> ModuleId {
+        let id = ModuleId(self.next_module);
+        self.next_module += 1;
+        self.modules.push(ModuleHooks {
+            id,
+            name: std::any::type_name::<M>(),
+            hooks: M::hooks(initialized),
+        });
+        if self.started {
+            self.run_start_hook(self.modules.len() - 1);
+        }
+        id
+    }
+
+    fn run_start_hook(&mut self, i: usize) {
+        // Taken out while it runs so the hook can borrow the robot
+        if let Some(mut on_start) = self.modules[i].hooks.on_start.take() {
+            on_start(self);
+            self.modules[i].hooks.on_start = Some(on_start);
+        }
+    }
+
+    /// Tears down a module using what its init returned, its hooks won't run again
+    pub fn remove_module<M: Module<T>, T>(&mut self, id: ModuleId, initialized: T) {
+        self.modules.retain(|hooks| hooks.id != id);
+        M::deinit(initialized, self);
+    }
+
+    /// Applies the queued ModuleCommands, commands queued while applying
+    /// are applied as well
+    pub(crate) fn apply_module_commands(&mut self) {
+        loop {
+            let queue = std::mem::take(&mut self.world.resource_mut::<ModuleCommands>().queue);
+            if queue.is_empty() {
+                return;
+            }
+            for command in queue {
+                command(self);
+            }
+        }
+    }
+
+    /// Swaps the lifecycle event buffers, events stay readable for the tick after they're sent
+    pub(crate) fn update_lifecycle_events(&mut self) {
+        self.world.resource_mut::<Events<SensorAdded>>().update();
+        self.world.resource_mut::<Events<SensorRemoved>>().update();
+        self.world.resource_mut::<Events<ActuatorAdded>>().update();
+        self.world
+            .resource_mut::<Events<ActuatorRemoved>>()
+            .update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[derive(Resource, Default)]
+    struct Seen {
+        added: Vec<Entity>,
+        removed: Vec<Entity>,
+    }
+
+    fn track(
+        mut seen: ResMut<Seen>,
+        mut added: EventReader<SensorAdded>,
+        mut removed: EventReader<SensorRemoved>,
+        mut actuator_added: EventReader<ActuatorAdded>,
+        mut actuator_removed: EventReader<ActuatorRemoved>,
+    ) {
+        seen.added.extend(added.iter().map(|e| e.0));
+        seen.removed.extend(removed.iter().map(|e| e.0));
+        seen.added.extend(actuator_added.iter().map(|e| e.0));
+        seen.removed.extend(actuator_removed.iter().map(|e| e.0));
+    }
+
+    /// Module that adds a sensor and a relay, and removes them again when torn down
+    struct Probe;
+
+    impl Module<(Entity, Entity)> for Probe {
+        fn init(self, robot: &mut Robot) -> (Entity, Entity) {
+            let sensor = SensorBuilder::new("Probe", robot)
+                .with_output(OutputType::TEMPERATURE)
+                .build();
+            let relay = ActuatorBuilder::new("Probe heater", robot)
+                .with_type(&ActuatorType::Relay)
+                .build();
+            (sensor, relay)
+        }
+
+        fn deinit((sensor, relay): (Entity, Entity), robot: &mut Robot) {
+            robot.remove_sensor(sensor).unwrap();
+            robot.remove_actuator(relay).unwrap();
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Plugged(Option<(ModuleId, (Entity, Entity))>);
+
+    /// Stands in for something watching a USB or I2C bus for devices
+    fn hotplug(
+        mut commands: ResMut<ModuleCommands>,
+        mut plugged: ResMut<Plugged>,
+        time: Res<Time>,
+    ) {
+        let tick = time.now().as_millis();
+        if tick == 1 {
+            commands.add_then(Probe, |robot, id, probe| {
+                robot.world.resource_mut::<Plugged>().0 = Some((id, probe));
+            });
+        } else if tick == 3 {
+            if let Some((id, probe)) = plugged.0.take() {
+                commands.remove::<Probe, _>(id, probe);
+            }
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct Log(Vec<&'static str>);
+
+    fn log(robot: &mut Robot, entry: &'static str) {
+        robot.resource_mut::<Log>().unwrap().0.push(entry);
+    }
+
+    struct Fan(&'static str);
+
+    impl Module<&'static str> for Fan {
+        fn init(self, _robot: &mut Robot) -> &'static str {
+            self.0
+        }
+
+        fn hooks(name: &&'static str) -> Hooks {
+            let name = *name;
+            Hooks::new()
+                .on_start(move |robot| log(robot, name))
+                .on_shutdown(move |robot| {
+                    log(robot, name);
+                    Ok(())
+                })
+        }
+    }
+
+    struct Heater;
+
+    impl Module<()> for Heater {
+        fn init(self, _robot: &mut Robot) {}
+
+        fn hooks(_: &()) -> Hooks {
+            Hooks::new()
+                .on_start(|robot| log(robot, "heater start"))
+                .on_shutdown(|robot| {
+                    log(robot, "heater stop");
+                    Err(Error::Timeout)
+                })
+        }
+    }
+
+    fn configure(mut log: ResMut<Log>) {
+        log.0.push("startup");
+    }
+
+    fn park(mut log: ResMut<Log>, mut actuators: Query<&mut Command>) {
+        log.0.push("shutdown");
+        for mut command in &mut actuators {
+            command.set(0.0);
+        }
+    }
+
+    #[test]
+    fn hooks_run_in_registration_order() {
+        let mut robot = Robot::new()
+            .with_resource(Log::default())
+            .with(Fan("intake fan"))
+            .with(Heater)
+            .with(Fan("exhaust fan"))
+            .with_system_in(RobotStage::Startup, configure)
+            .with_system_in(RobotStage::Shutdown, park);
+        let pump = ActuatorBuilder::new("Pump", &mut robot)
+            .with_setpoint(1.0)
+            .build();
+
+        robot.run();
+        robot.run();
+        assert_eq!(
+            robot.resource::<Log>().unwrap().0,
+            ["intake fan", "heater start", "exhaust fan", "startup"]
+        );
+
+        let report = robot.shutdown();
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.failures,
+            [(std::any::type_name::<Heater>(), Error::Timeout)]
+        );
+        assert_eq!(robot.world.get::<Setpoint>(pump).unwrap().0, 0.0);
+        assert_eq!(
+            robot.resource::<Log>().unwrap().0[4..],
+            ["shutdown", "intake fan", "heater stop", "exhaust fan"]
+        );
+    }
+
+    /// Module whose hardware never answers while it's being parked
+    struct Stuck;
+
+    impl Module<()> for Stuck {
+        fn init(self, _robot: &mut Robot) {}
+
+        fn hooks(_: &()) -> Hooks {
+            Hooks::new().on_shutdown(|_| {
+                std::thread::sleep(std::time::Duration::from_secs(10));
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn stuck_hooks_exit_the_process() {
+        // The exit can only be observed from outside, so the test runs itself in a child process
+        if std::env::var_os("ROBOTRS_STUCK_HOOK").is_some() {
+            let mut robot = Robot::new()
+                .with(Stuck)
+                .with_shutdown_timeout(Duration::Millis(10));
+            robot.run();
+            robot.shutdown();
+            unreachable!("the watchdog exits the process");
+        }
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "modules::lifecycle::tests::stuck_hooks_exit_the_process",
+            ])
+            .env("ROBOTRS_STUCK_HOOK", "1")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(1));
+    }
+
+    #[test]
+    fn removed_modules_lose_their_hooks() {
+        let mut robot = Robot::new().with_resource(Log::default());
+        for _ in 0..3 {
+            let (id, name) = robot.add_with_id(Fan("unplugged fan"));
+            robot.run();
+            robot.remove_module::<Fan, _>(id, name);
+        }
+        assert!(robot.modules.is_empty());
+
+        assert!(robot.shutdown().is_clean());
+        assert_eq!(robot.resource::<Log>().unwrap().0, ["unplugged fan"; 3]);
+    }
+
+    #[test]
+    fn modules_come_and_go_between_ticks() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new()
+            .with_clock(clock.clone())
+            .with_system_in(RobotStage::Input, hotplug)
+            .with_system_in(RobotStage::Output, track);
+        robot.world.init_resource::<Seen>();
+        robot.world.init_resource::<Plugged>();
+
+        let step = |robot: &mut Robot| {
+            clock.advance(std::time::Duration::from_millis(1));
+            robot.run();
+        };
+
+        // Queued on tick 1, built before tick 2
+        step(&mut robot);
+        assert!(robot.world.resource::<Seen>().added.is_empty());
+        step(&mut robot);
+        let (_, (sensor, relay)) = robot.world.resource::<Plugged>().0.unwrap();
+        assert_eq!(robot.world.resource::<Seen>().added, vec![sensor, relay]);
+        assert!(robot.world.get::<Features>(sensor).is_some());
+        assert!(robot.world.get::<Setpoint>(relay).is_some());
+
+        // Removal queued on tick 3, applied before tick 4
+        step(&mut robot);
+        step(&mut robot);
+        assert!(robot.world.get_entity(sensor).is_none());
+        assert!(robot.world.get_entity(relay).is_none());
+        assert_eq!(robot.world.resource::<Seen>().removed, vec![sensor, relay]);
+        assert_eq!(robot.world.resource::<Seen>().added, vec![sensor, relay]);
+        assert_eq!(robot.remove_actuator(relay), Err(Error::NotAnActuator));
+        assert!(robot.world.resource::<ModuleCommands>().is_empty());
+    }
+}
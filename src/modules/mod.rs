@@ -1,9 +1,23 @@
+use crate::error::Error;
+use crate::modules::lifecycle::Hooks;
+use crate::modules::unit::Unit;
 use crate::Robot;
 use bevy_ecs::prelude::*;
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 
+pub mod actuator;
+pub mod clock;
+pub mod fault;
+pub mod handle;
+pub mod lifecycle;
+pub mod names;
 pub mod output;
 pub mod sensor;
 pub mod timer;
+pub mod unit;
 
 /// Here you will take care of initializing all your Sensors and Features
 pub trait Module<T> {
@@ -11,20 +25,80 @@ pub trait Module<T> {
     /// you can optionally return information like entities
     /// for when users want to use that specific sensor/feature
     fn init(self, robot: &mut Robot) -> T;
+
+    /// Tears down what init set up, called when the module is removed
+    /// from a running robot, by default nothing is removed
+    fn deinit(_initialized: T, _robot: &mut Robot) {}
+
+    /// Start and shutdown hooks built from what init returned, every module that's added
+    /// gets its own and they run in the order the modules were added
+    fn hooks(_initialized: &T) -> Hooks {
+        Hooks::new()
+    }
+}
+
+/// Namespaced identifier written as vendor/module/name,
+/// third party modules should use their crate's name as the vendor so ids never clash
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Id {
+    vendor: Cow<'static, str>,
+    module: Cow<'static, str>,
+    name: Cow<'static, str>,
+}
+
+impl Id {
+    pub const fn new(vendor: &'static str, module: &'static str, name: &'static str) -> Self {
+        Self {
+            vendor: Cow::Borrowed(vendor),
+            module: Cow::Borrowed(module),
+            name: Cow::Borrowed(name),
+        }
+    }
+
+    /// Same as new for parts that are only known at runtime
+    pub fn from_parts(vendor: &str, module: &str, name: &str) -> Self {
+        Self {
+            vendor: Cow::Owned(vendor.to_string()),
+            module: Cow::Owned(module.to_string()),
+            name: Cow::Owned(name.to_string()),
+        }
+    }
+
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.vendor, self.module, self.name)
+    }
 }
 
 #[derive(Component, Debug)]
 pub struct Metadata {
-    pub id: u8,
+    pub id: Id,
     pub name: String,
     pub description: String,
 }
 
 /// Describes the current entity being used, its very useful for logging
 pub trait Descriptor {
-    fn id(&self) -> u8;
+    fn id(&self) -> Id;
     fn name(&self) -> String;
     fn description(&self) -> String;
+    /// Unit the entity's values are expressed in
+    fn unit(&self) -> Unit {
+        Unit::Unitless
+    }
     fn metadata(&self) -> Metadata {
         Metadata {
             id: self.id(),
@@ -38,8 +112,8 @@ pub trait Descriptor {
 pub struct UndefinedType;
 
 impl Descriptor for UndefinedType {
-    fn id(&self) -> u8 {
-        255
+    fn id(&self) -> Id {
+        Id::new("robotrs", "core", "undefined")
     }
 
     fn name(&self) -> String {
@@ -50,3 +124,142 @@ impl Descriptor for UndefinedType {
         "N/A".to_string()
     }
 }
+
+/// Every Descriptor id in use along with the type that claimed it,
+/// many entities can share a type but two types can't share an id
+#[derive(Resource, Default, Debug)]
+pub struct Descriptors {
+    ids: HashMap<Id, (TypeId, &'static str)>,
+}
+
+impl Descriptors {
+    /// Claims the descriptor's id for its type, fails if another type already uses it
+    pub fn register<T: Descriptor + 'static>(&mut self, descriptor: &T) -> Result<(), Error> {
+        self.claim(
+            descriptor.id(),
+            TypeId::of::<T>(),
+            std::any::type_name::<T>(),
+        )
+    }
+
+    pub(crate) fn claim(
+        &mut self,
+        id: Id,
+        type_id: TypeId,
+        type_name: &'static str,
+    ) -> Result<(), Error> {
+        match self.ids.get(&id) {
+            Some((owner, name)) if *owner != type_id => Err(Error::DuplicateId(name)),
+            Some(_) => Ok(()),
+            None => {
+                self.ids.insert(id, (type_id, type_name));
+                Ok(())
+            }
+        }
+    }
+
+    /// Name of the type that uses the id
+    pub fn type_name(&self, id: &Id) -> Option<&'static str> {
+        self.ids.get(id).map(|(_, name)| *name)
+    }
+}
+
+impl Robot {
+    /// Claims a descriptor's id, see Descriptors::register
+    pub fn register_descriptor<T: Descriptor + 'static>(
+        &mut self,
+        descriptor: &T,
+    ) -> Result<(), Error> {
+        self.world
+            .get_resource_or_insert_with(Descriptors::default)
+            .register(descriptor)
+    }
+
+    /// Id, name and description of a sensor, actuator or output
+    pub fn metadata(&self, entity: Entity) -> Option<&Metadata> {
+        self.world.get::<Metadata>(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    struct Probe;
+    struct OtherProbe;
+
+    impl Descriptor for Probe {
+        fn id(&self) -> Id {
+            Id::new("acme", "sensors", "probe")
+        }
+
+        fn name(&self) -> String {
+            "Probe".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Probe".to_string()
+        }
+    }
+
+    impl Descriptor for OtherProbe {
+        fn id(&self) -> Id {
+            Id::new("acme", "sensors", "probe")
+        }
+
+        fn name(&self) -> String {
+            "Other probe".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Another vendor's probe with a clashing id".to_string()
+        }
+    }
+
+    #[test]
+    fn ids_are_namespaced() {
+        let id = Id::from_parts("acme", "sensors", "probe");
+        assert_eq!(id, Probe.id());
+        assert_eq!(id.to_string(), "acme/sensors/probe");
+        assert_ne!(Id::new("other", "sensors", "probe"), id);
+    }
+
+    #[test]
+    fn types_can_not_share_an_id() {
+        let mut robot = Robot::new();
+        let first = SensorBuilder::new("First", &mut robot)
+            .with_type(&Probe)
+            .try_build();
+        let second = SensorBuilder::new("Second", &mut robot)
+            .with_type(&Probe)
+            .try_build();
+        assert!(first.is_ok() && second.is_ok());
+
+        let clash = SensorBuilder::new("Clash", &mut robot)
+            .with_type(&OtherProbe)
+            .try_build();
+        let owner = std::any::type_name::<Probe>();
+        assert_eq!(clash, Err(Error::DuplicateId(owner)));
+        assert_eq!(
+            robot.register_descriptor(&OtherProbe),
+            Err(Error::DuplicateId(owner))
+        );
+        assert_eq!(
+            robot.world.resource::<Descriptors>().type_name(&Probe.id()),
+            Some(std::any::type_name::<Probe>())
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "failed to build actuator Clash (acme/sensors/probe): id already used by robotrs::modules::tests::Probe"
+    )]
+    fn collisions_panic_on_build() {
+        let mut robot = Robot::new();
+        robot.register_descriptor(&Probe).unwrap();
+        ActuatorBuilder::new("Clash", &mut robot)
+            .with_type(&OtherProbe)
+            .build();
+    }
+}
@@ -0,0 +1,135 @@
+//! Looking sensors up by name
+
+use crate::error::Error;
+use crate::modules::output::{OutputType, Reading, SensorOutputs};
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
+use std::collections::HashMap;
+
+/// Index of every sensor by name, names are unique so config files
+/// and command lines can refer to sensors by them
+#[derive(Resource, Default, Debug)]
+pub struct SensorNames {
+    names: HashMap<String, Entity>,
+}
+
+impl SensorNames {
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.names.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Every name with its sensor, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Entity)> {
+        self.names
+            .iter()
+            .map(|(name, sensor)| (name.as_str(), *sensor))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Fails if another sensor already goes by that name
+    pub(crate) fn claim(&mut self, name: &str, sensor: Entity) -> Result<(), Error> {
+        match self.names.get(name) {
+            Some(other) if *other != sensor => Err(Error::DuplicateName),
+            Some(_) => Ok(()),
+            None => {
+                self.names.insert(name.to_string(), sensor);
+                Ok(())
+            }
+        }
+    }
+
+    /// Frees the sensor's name
+    pub(crate) fn release(&mut self, sensor: Entity) {
+        self.names.retain(|_, other| *other != sensor);
+    }
+}
+
+/// Looks sensors and their readings up by name inside systems
+#[derive(SystemParam)]
+pub struct SensorLookup<'w, 's> {
+    names: Res<'w, SensorNames>,
+    outputs: SensorOutputs<'w, 's>,
+}
+
+impl<'w, 's> SensorLookup<'w, 's> {
+    pub fn sensor(&self, name: &str) -> Result<Entity, Error> {
+        self.names.get(name).ok_or(Error::UnknownSensor)
+    }
+
+    /// Reading of the named sensor's first output of that type
+    pub fn reading(&self, name: &str, kind: OutputType) -> Result<&Reading, Error> {
+        self.outputs.get(self.sensor(name)?, kind)
+    }
+
+    pub fn names(&self) -> &SensorNames {
+        &self.names
+    }
+}
+
+impl Robot {
+    pub fn sensor_by_name(&self, name: &str) -> Option<Entity> {
+        self.world.resource::<SensorNames>().get(name)
+    }
+
+    pub fn sensor_names(&self) -> &SensorNames {
+        self.world.resource::<SensorNames>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use bevy_ecs::system::SystemState;
+
+    #[test]
+    fn names_are_unique() {
+        let mut robot = Robot::new();
+        let pump = SensorBuilder::new("Pump", &mut robot).build();
+        assert_eq!(robot.sensor_by_name("Pump"), Some(pump));
+        assert_eq!(robot.sensor_by_name("Fan"), None);
+
+        let duplicate = SensorBuilder::new("Pump", &mut robot).try_build();
+        assert_eq!(duplicate, Err(Error::DuplicateName));
+        assert_eq!(robot.sensor_names().len(), 1);
+
+        // The name can be reused once the sensor is gone
+        robot.remove_sensor(pump).unwrap();
+        assert_eq!(robot.sensor_by_name("Pump"), None);
+        let pump = SensorBuilder::new("Pump", &mut robot).build();
+        assert_eq!(robot.sensor_by_name("Pump"), Some(pump));
+    }
+
+    #[test]
+    fn systems_look_readings_up_by_name() {
+        let mut robot = Robot::new();
+        let probe = SensorBuilder::new("Probe", &mut robot)
+            .with_output(OutputType::TEMPERATURE)
+            .build();
+        let output = robot.world.get::<Features>(probe).unwrap()[0];
+        robot.world.get_mut::<Reading>(output).unwrap().set(21.0);
+
+        let mut state = SystemState::<SensorLookup>::new(&mut robot.world);
+        let lookup = state.get_mut(&mut robot.world);
+
+        assert_eq!(lookup.sensor("Probe"), Ok(probe));
+        let reading = lookup.reading("Probe", OutputType::TEMPERATURE).unwrap();
+        assert_eq!((reading.value(), reading.unit()), (21.0, Unit::Celsius));
+        assert_eq!(
+            lookup.reading("Probe", OutputType::HUMIDITY).err(),
+            Some(Error::MissingOutput(OutputType::HUMIDITY))
+        );
+        assert_eq!(lookup.sensor("Fan"), Err(Error::UnknownSensor));
+    }
+}
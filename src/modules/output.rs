@@ -1,38 +1,91 @@
-use crate::modules::{Descriptor, Metadata};
-use crate::UndefinedType;
+use crate::error::Error;
+use crate::modules::sensor::Features;
+use crate::modules::{
+    clock::Time, timer::Duration, unit::Unit, Descriptor, Descriptors, Id, Metadata,
+};
+use crate::{Robot, UndefinedType};
 use bevy_ecs::prelude::*;
+use bevy_ecs::system::SystemParam;
 use std::ops::Deref;
 
 /// Output setup helper
 /// Each Output can be seen as its own Entity,
 /// meaning that each of the Sensor's readings can be considered separate from it
 pub struct OutputBuilder {
-    metadata: Metadata,
+    output_type: Option<OutputType>,
+    unit: Option<Unit>,
+    max_age: Option<Duration>,
+}
+
+impl Default for OutputBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OutputBuilder {
     /// Creates an Undefined output, an output without any metadata
     pub fn new() -> Self {
         Self {
-            metadata: UndefinedType.metadata(),
+            output_type: None,
+            unit: None,
+            max_age: None,
         }
     }
 
-    /// Define the output's type
-    pub fn with_type<T: Descriptor + 'static>(mut self, output_type: T) -> Self {
-        self.metadata = output_type.metadata();
+    /// Define the output's type, readings default to the type's unit
+    pub fn with_type(mut self, output_type: OutputType) -> Self {
+        self.output_type = Some(output_type);
+        self
+    }
+
+    /// Overrides the unit readings are stored in
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = Some(unit);
         self
     }
 
-    /// Handles setting up all the necessary output components
+    /// Marks the reading as stale when it goes longer than max_age without an update
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Handles setting up all the necessary output components,
+    /// the type's metadata and range are looked up in OutputTypes
     pub fn build(self, sensor: &Entity, world: &mut World) -> Entity {
-        world
-            .spawn(OutputBundle {
-                output: Output(sensor.clone()),
-                meta: self.metadata,
-                reading: Reading::default(),
-            })
-            .id()
+        let types = world.get_resource_or_insert_with(OutputTypes::default);
+        let kind = self.output_type.and_then(|t| types.get(t));
+
+        let meta = kind.map_or_else(|| UndefinedType.metadata(), Descriptor::metadata);
+        let unit = self
+            .unit
+            .or(kind.map(|kind| kind.unit))
+            .unwrap_or(Unit::Unitless);
+        // The range is kept in the output's unit so readings can be checked as they're set
+        let range = kind.and_then(|kind| {
+            let (min, max) = kind.range?;
+            Some((kind.unit.convert(min, unit)?, kind.unit.convert(max, unit)?))
+        });
+
+        let mut output = world.spawn(OutputBundle {
+            output: Output(*sensor),
+            meta,
+            reading: Reading {
+                unit,
+                range,
+                ..Default::default()
+            },
+        });
+
+        if let Some(output_type) = self.output_type {
+            output.insert(output_type);
+        }
+        if let Some(max_age) = self.max_age {
+            output.insert(MaxAge(max_age.into()));
+        }
+
+        output.id()
     }
 }
 
@@ -44,8 +97,8 @@ pub struct OutputBundle {
 }
 
 #[derive(Component, Debug)]
-/// Output identifier that contains its host's id
-pub struct Output(pub Entity);
+/// Output identifier that contains its host's id, the sensor is its parent
+pub struct Output(pub(crate) Entity);
 impl Deref for Output {
     type Target = Entity;
 
@@ -56,51 +109,709 @@ impl Deref for Output {
 
 #[derive(Component, Default, Debug)]
 /// Output's reading
-pub struct Reading(pub f64);
+pub struct Reading {
+    value: f64,
+    status: Status,
+    sequence: u64,
+    timestamp: Option<std::time::Duration>,
+    // Set when the value changed and still needs its timestamp
+    pending: bool,
+    unit: Unit,
+    range: Option<(f64, f64)>,
+}
+
 impl Reading {
+    /// Stores a new value, it's only valid if it fits in the output type's range
     pub fn set(&mut self, n: f64) {
-        self.0 = n;
+        self.value = n;
+        self.status = match self.range {
+            Some((min, max)) if !(min..=max).contains(&n) => Status::Error(Error::OutOfRange),
+            _ => Status::Valid,
+        };
+        self.sequence += 1;
+        self.pending = true;
+    }
+
+    /// Sets a value expressed in `from`, converting it into the output's unit
+    pub fn set_as(&mut self, n: f64, from: Unit) -> Option<()> {
+        self.set(from.convert(n, self.unit)?);
+        Some(())
+    }
+
+    /// Flags the reading as failed, the last value is kept
+    pub fn fail(&mut self, error: Error) {
+        self.status = Status::Error(error);
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Gets the value in the requested unit,
+    /// returns None if the output's unit can't be converted into it
+    pub fn get_as(&self, to: Unit) -> Option<f64> {
+        self.unit.convert(self.value, to)
+    }
+
+    /// Unit the value is stored in, the same as the output's Unit
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.status == Status::Valid
+    }
+
+    /// Increases every time a value is set, 0 means it was never set
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Valid values in the output's unit, if its type has a range
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.range
+    }
+
+    /// Robot time of the last update, assigned at the end of the run the value was set in
+    pub fn timestamp(&self) -> Option<std::time::Duration> {
+        self.timestamp
     }
 }
+
 impl Deref for Reading {
     type Target = f64;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.value
     }
 }
 
-#[derive(Component, Default, Debug)]
-/// Output metadata descriptor
-pub enum OutputType {
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Status {
+    /// The value was never set
     #[default]
-    Temperature,
-    Humidity,
-    Moisture,
+    Uninitialised,
+    Valid,
+    /// The value is older than the output's MaxAge
+    Stale,
+    /// The last read failed, the value is from the last successful read
+    Error(Error),
+}
+
+#[derive(Component, Debug)]
+/// Maximum time a reading can go without an update before being marked as stale
+pub struct MaxAge(pub std::time::Duration);
+
+/// Looks up readings by their output type instead of walking a sensor's Features
+#[derive(SystemParam)]
+pub struct SensorOutputs<'w, 's> {
+    sensors: Query<'w, 's, &'static Features>,
+    outputs: Query<'w, 's, (&'static Output, &'static OutputType, &'static Reading)>,
 }
 
-impl Descriptor for OutputType {
-    fn id(&self) -> u8 {
-        match self {
-            OutputType::Temperature => 0,
-            OutputType::Humidity => 1,
-            OutputType::Moisture => 2,
+impl<'w, 's> SensorOutputs<'w, 's> {
+    /// Every output of that type on the sensor, in the order they were added
+    pub fn outputs(
+        &self,
+        sensor: Entity,
+        kind: OutputType,
+    ) -> Result<impl Iterator<Item = Entity> + '_, Error> {
+        let features = self.sensors.get(sensor).map_err(|_| Error::NotASensor)?;
+        Ok(features.iter().copied().filter(move |output| {
+            matches!(self.outputs.get(*output), Ok((_, output_type, _)) if *output_type == kind)
+        }))
+    }
+
+    /// First output of that type on the sensor
+    pub fn output(&self, sensor: Entity, kind: OutputType) -> Result<Entity, Error> {
+        self.outputs(sensor, kind)?
+            .next()
+            .ok_or(Error::MissingOutput(kind))
+    }
+
+    /// Reading of the sensor's first output of that type
+    pub fn get(&self, sensor: Entity, kind: OutputType) -> Result<&Reading, Error> {
+        let features = self.sensors.get(sensor).map_err(|_| Error::NotASensor)?;
+        features
+            .iter()
+            .find_map(|output| match self.outputs.get(*output) {
+                Ok((_, output_type, reading)) if *output_type == kind => Some(reading),
+                _ => None,
+            })
+            .ok_or(Error::MissingOutput(kind))
+    }
+
+    /// Every output of that type across all sensors, along with the sensor it belongs to
+    pub fn iter(&self, kind: OutputType) -> impl Iterator<Item = (Entity, &Reading)> {
+        self.outputs
+            .iter()
+            .filter(move |(_, output_type, _)| **output_type == kind)
+            .map(|(sensor, _, reading)| (sensor.0, reading))
+    }
+}
+
+/// Timestamps the readings that were set this run and flags the stale ones
+pub(crate) fn reading_system(time: Res<Time>, mut query: Query<(&mut Reading, Option<&MaxAge>)>) {
+    for (mut reading, max_age) in &mut query {
+        if reading.pending {
+            reading.pending = false;
+            reading.timestamp = Some(time.now());
+        }
+
+        if let (Some(max_age), Some(timestamp)) = (max_age, reading.timestamp) {
+            let age = time.now().saturating_sub(timestamp);
+            if reading.status == Status::Valid && age > max_age.0 {
+                reading.status = Status::Stale;
+            }
         }
     }
+}
 
-    fn name(&self) -> String {
-        match self {
-            OutputType::Temperature => "Temperature".to_string(),
-            OutputType::Humidity => "Humidity".to_string(),
-            OutputType::Moisture => "Moisture".to_string(),
+/// Identifies a kind of output registered in OutputTypes,
+/// the built in kinds always have the same ids and custom ones get the ids after them
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct OutputType(u8);
+
+impl OutputType {
+    pub const TEMPERATURE: OutputType = OutputType(0);
+    pub const HUMIDITY: OutputType = OutputType(1);
+    pub const MOISTURE: OutputType = OutputType(2);
+    pub const PRESSURE: OutputType = OutputType(3);
+    pub const DISTANCE: OutputType = OutputType(4);
+    pub const ACCELERATION: OutputType = OutputType(5);
+    pub const ANGULAR_VELOCITY: OutputType = OutputType(6);
+    pub const VOLTAGE: OutputType = OutputType(7);
+    pub const CURRENT: OutputType = OutputType(8);
+    pub const POWER: OutputType = OutputType(9);
+    pub const ILLUMINANCE: OutputType = OutputType(10);
+    pub const GAS_CONCENTRATION: OutputType = OutputType(11);
+    pub const SPEED: OutputType = OutputType(12);
+    pub const ANGLE: OutputType = OutputType(13);
+    pub const MAGNETIC_FIELD: OutputType = OutputType(14);
+    pub const MASS: OutputType = OutputType(15);
+    pub const FORCE: OutputType = OutputType(16);
+    pub const FREQUENCY: OutputType = OutputType(17);
+    pub const SOUND_LEVEL: OutputType = OutputType(18);
+
+    pub fn id(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Everything known about a kind of output
+#[derive(Clone, PartialEq, Debug)]
+pub struct OutputKind {
+    /// Namespaced id, claimed in Descriptors when the kind is registered
+    pub id: Id,
+    pub name: String,
+    pub description: String,
+    /// Unit readings are stored in unless the output overrides it
+    pub unit: Unit,
+    /// Smallest and largest valid values expressed in the kind's unit
+    pub range: Option<(f64, f64)>,
+}
+
+impl OutputKind {
+    pub fn new(id: Id, name: &str, description: &str, unit: Unit) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            description: description.to_string(),
+            unit,
+            range: None,
         }
     }
 
+    /// Values outside of min and max are flagged as out of range,
+    /// use infinity for a bound that doesn't exist
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+}
+
+impl Descriptor for OutputKind {
+    fn id(&self) -> Id {
+        self.id.clone()
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
     fn description(&self) -> String {
-        match self {
-            OutputType::Temperature => "Description".to_string(),
-            OutputType::Humidity => "Description".to_string(),
-            OutputType::Moisture => "Description".to_string(),
+        self.description.clone()
+    }
+
+    fn unit(&self) -> Unit {
+        self.unit
+    }
+}
+
+/// Registry of every kind of output, modules register their own kinds
+/// on top of the built in ones with Robot::register_output_type
+#[derive(Resource, Debug)]
+pub struct OutputTypes {
+    kinds: Vec<OutputKind>,
+}
+
+impl Default for OutputTypes {
+    fn default() -> Self {
+        use Unit::*;
+        const INF: f64 = f64::INFINITY;
+
+        let robotrs = |name| Id::new("robotrs", "output", name);
+
+        let mut types = Self { kinds: vec![] };
+        for kind in [
+            OutputKind::new(
+                robotrs("temperature"),
+                "Temperature",
+                "Temperature of the air, a surface or a probe",
+                Celsius,
+            )
+            .with_range(-273.15, INF),
+            OutputKind::new(
+                robotrs("humidity"),
+                "Humidity",
+                "Relative humidity of the air",
+                RelativeHumidity,
+            )
+            .with_range(0.0, 100.0),
+            OutputKind::new(
+                robotrs("moisture"),
+                "Moisture",
+                "Water content of a material like soil",
+                Percent,
+            )
+            .with_range(0.0, 100.0),
+            OutputKind::new(robotrs("pressure"), "Pressure", "Absolute pressure", Pascal)
+                .with_range(0.0, INF),
+            OutputKind::new(
+                robotrs("distance"),
+                "Distance",
+                "Distance to an object",
+                Meter,
+            )
+            .with_range(0.0, INF),
+            OutputKind::new(
+                robotrs("acceleration"),
+                "Acceleration",
+                "Linear acceleration",
+                MetersPerSecondSquared,
+            ),
+            OutputKind::new(
+                robotrs("angular_velocity"),
+                "Angular Velocity",
+                "Rate of rotation",
+                DegreesPerSecond,
+            ),
+            OutputKind::new(
+                robotrs("voltage"),
+                "Voltage",
+                "Electric potential difference",
+                Volt,
+            ),
+            OutputKind::new(robotrs("current"), "Current", "Electric current", Ampere),
+            OutputKind::new(robotrs("power"), "Power", "Electric power", Watt),
+            OutputKind::new(
+                robotrs("illuminance"),
+                "Illuminance",
+                "Light falling on a surface",
+                Lux,
+            )
+            .with_range(0.0, INF),
+            OutputKind::new(
+                robotrs("gas_concentration"),
+                "Gas Concentration",
+                "Amount of a gas in the air",
+                PartsPerMillion,
+            )
+            .with_range(0.0, 1_000_000.0),
+            OutputKind::new(robotrs("speed"), "Speed", "Linear speed", MetersPerSecond),
+            OutputKind::new(
+                robotrs("angle"),
+                "Angle",
+                "Orientation or position of a joint",
+                Degree,
+            ),
+            OutputKind::new(
+                robotrs("magnetic_field"),
+                "Magnetic Field",
+                "Magnetic flux density",
+                Microtesla,
+            ),
+            OutputKind::new(
+                robotrs("mass"),
+                "Mass",
+                "Mass or weight of a load",
+                Kilogram,
+            ),
+            OutputKind::new(robotrs("force"), "Force", "Applied force", Newton),
+            OutputKind::new(
+                robotrs("frequency"),
+                "Frequency",
+                "Rate of a periodic signal",
+                Hertz,
+            )
+            .with_range(0.0, INF),
+            OutputKind::new(
+                robotrs("sound_level"),
+                "Sound Level",
+                "Sound pressure level",
+                Decibel,
+            ),
+        ] {
+            types
+                .register(kind)
+                .expect("built in output types are unique");
         }
+        types
+    }
+}
+
+impl OutputTypes {
+    /// Registers a new kind and returns its OutputType, kinds are identified by their Id
+    /// so registering the same kind twice returns the OutputType it was first given.
+    /// Fails if a kind with that Id was registered with another name, description, unit
+    /// or range, or when every OutputType is taken
+    pub(crate) fn register(&mut self, kind: OutputKind) -> Result<OutputType, Error> {
+        if let Some(output_type) = self.find_id(&kind.id) {
+            return match self.get(output_type) {
+                Some(existing) if *existing == kind => Ok(output_type),
+                _ => Err(Error::DuplicateId(std::any::type_name::<OutputKind>())),
+            };
+        }
+
+        // 255 belongs to UndefinedType
+        let id = self.kinds.len();
+        if id >= 255 {
+            return Err(Error::TooManyOutputTypes);
+        }
+        self.kinds.push(kind);
+        Ok(OutputType(id as u8))
+    }
+
+    pub fn get(&self, output_type: OutputType) -> Option<&OutputKind> {
+        self.kinds.get(output_type.id() as usize)
+    }
+
+    /// Looks up a kind by its name, the first one registered wins if vendors share a name
+    pub fn find(&self, name: &str) -> Option<OutputType> {
+        self.kinds
+            .iter()
+            .position(|kind| kind.name == name)
+            .map(|id| OutputType(id as u8))
+    }
+
+    pub fn find_id(&self, id: &Id) -> Option<OutputType> {
+        self.kinds
+            .iter()
+            .position(|kind| kind.id == *id)
+            .map(|id| OutputType(id as u8))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (OutputType, &OutputKind)> {
+        self.kinds
+            .iter()
+            .enumerate()
+            .map(|(id, kind)| (OutputType(id as u8), kind))
+    }
+}
+
+impl Robot {
+    /// Registers a kind of output and claims its Id, see OutputTypes::register.
+    /// Fails as well if a sensor or actuator type already uses the Id
+    pub fn register_output_type(&mut self, kind: OutputKind) -> Result<OutputType, Error> {
+        self.world
+            .get_resource_or_insert_with(Descriptors::default)
+            .register(&kind)?;
+        self.world.resource_mut::<OutputTypes>().register(kind)
+    }
+
+    pub fn output_types(&self) -> &OutputTypes {
+        self.world.resource::<OutputTypes>()
+    }
+
+    /// Latest reading of an output, None if the entity isn't an output
+    pub fn reading(&self, output: Entity) -> Option<&Reading> {
+        self.world.get::<Reading>(output)
+    }
+
+    /// Every output of the sensor with its reading, in the order they were added
+    pub fn readings_of(&self, sensor: Entity) -> Result<Vec<(Entity, &Reading)>, Error> {
+        let features = self
+            .world
+            .get::<Features>(sensor)
+            .ok_or(Error::NotASensor)?;
+        Ok(features
+            .iter()
+            .filter_map(|output| Some((*output, self.world.get::<Reading>(*output)?)))
+            .collect())
+    }
+
+    /// Stores a value in an output from outside the schedule, mostly useful in tests
+    pub fn set_reading(&mut self, output: Entity, value: f64) -> Result<(), Error> {
+        let mut reading = self
+            .world
+            .get_mut::<Reading>(output)
+            .ok_or(Error::NotAnOutput)?;
+        reading.set(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::clock::MockClock;
+    use crate::prelude::*;
+
+    const MS: std::time::Duration = std::time::Duration::from_millis(1);
+
+    #[derive(Resource, Default)]
+    struct Paused(bool);
+
+    fn simulate_readings(paused: Res<Paused>, mut query: Query<&mut Reading>) {
+        if paused.0 {
+            return;
+        }
+        for mut reading in &mut query {
+            let n = reading.value() + 1.0;
+            reading.set(n);
+        }
+    }
+
+    #[test]
+    fn readings_are_stamped_and_go_stale() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new().with_clock(clock.clone());
+        robot.world.init_resource::<Paused>();
+        robot.world.resource_mut::<Paused>().0 = true;
+
+        let sensor = SensorBuilder::new("Sensor", &mut robot)
+            .with_custom_output(
+                OutputBuilder::new()
+                    .with_type(OutputType::MOISTURE)
+                    .with_max_age(Duration::Millis(5)),
+            )
+            .with_system(simulate_readings)
+            .build();
+        let output = robot.world.get::<Features>(sensor).unwrap().0[0];
+        fn reading(robot: &Robot, output: Entity) -> &Reading {
+            robot.world.get::<Reading>(output).unwrap()
+        }
+
+        robot.run();
+        assert_eq!(reading(&robot, output).status(), Status::Uninitialised);
+        assert_eq!(reading(&robot, output).timestamp(), None);
+
+        robot.world.resource_mut::<Paused>().0 = false;
+        clock.advance(MS * 2);
+        robot.run();
+        assert!(reading(&robot, output).is_valid());
+        assert_eq!(reading(&robot, output).sequence(), 1);
+        assert_eq!(reading(&robot, output).timestamp(), Some(MS * 2));
+
+        robot.world.resource_mut::<Paused>().0 = true;
+        clock.advance(MS * 5);
+        robot.run();
+        assert!(reading(&robot, output).is_valid());
+        clock.advance(MS);
+        robot.run();
+        assert_eq!(reading(&robot, output).status(), Status::Stale);
+        assert_eq!(reading(&robot, output).sequence(), 1);
+    }
+
+    #[test]
+    fn custom_types_get_their_own_ids() {
+        let mut robot = Robot::new();
+        let count = robot.output_types().iter().count();
+
+        let ph = OutputKind::new(
+            Id::new("acme", "water", "ph"),
+            "pH",
+            "Acidity of a solution",
+            Unit::Unitless,
+        )
+        .with_range(0.0, 14.0);
+        let first = robot.register_output_type(ph.clone()).unwrap();
+        let again = robot.register_output_type(ph.clone()).unwrap();
+        let turbidity = robot
+            .register_output_type(OutputKind::new(
+                Id::new("acme", "water", "turbidity"),
+                "Turbidity",
+                "Cloudiness of a liquid",
+                Unit::Unitless,
+            ))
+            .unwrap();
+
+        // Same id but a different definition
+        let clash = robot.register_output_type(ph.with_range(0.0, 10.0));
+        assert_eq!(
+            clash,
+            Err(Error::DuplicateId(std::any::type_name::<OutputKind>()))
+        );
+        // Ids are shared with the sensor and actuator types
+        robot.register_descriptor(&UndefinedType).unwrap();
+        let clash = robot.register_output_type(OutputKind::new(
+            UndefinedType.id(),
+            "Undefined",
+            "Clashes with a sensor type",
+            Unit::Unitless,
+        ));
+        assert_eq!(
+            clash,
+            Err(Error::DuplicateId(std::any::type_name::<UndefinedType>()))
+        );
+
+        assert_eq!(first.id() as usize, count);
+        assert_eq!(again, first);
+        assert_ne!(turbidity, first);
+        assert_eq!(
+            robot.output_types().find("Pressure"),
+            Some(OutputType::PRESSURE)
+        );
+        assert_eq!(robot.output_types().get(first).unwrap().name, "pH");
+
+        let sensor = SensorBuilder::new("Probe", &mut robot)
+            .with_output(first)
+            .build();
+        let output = robot.world.get::<Features>(sensor).unwrap()[0];
+        assert_eq!(
+            robot.metadata(output).unwrap().id.to_string(),
+            "acme/water/ph"
+        );
+    }
+
+    #[test]
+    fn output_type_ids_run_out() {
+        let mut types = OutputTypes::default();
+        let left = 255 - types.iter().count();
+        for i in 0..left {
+            let name = format!("channel_{}", i);
+            let id = Id::from_parts("acme", "adc", &name);
+            let kind = OutputKind::new(id, &name, "Raw channel", Unit::Unitless);
+            assert!(types.register(kind).is_ok());
+        }
+
+        let id = Id::new("acme", "adc", "one_too_many");
+        let kind = OutputKind::new(id, "One too many", "Raw channel", Unit::Unitless);
+        assert_eq!(types.register(kind), Err(Error::TooManyOutputTypes));
+    }
+
+    #[test]
+    fn outputs_use_their_type() {
+        let mut robot = Robot::new();
+        let sensor = SensorBuilder::new("Sensor", &mut robot)
+            .with_output(OutputType::HUMIDITY)
+            .with_custom_output(
+                OutputBuilder::new()
+                    .with_type(OutputType::TEMPERATURE)
+                    .with_unit(Unit::Kelvin),
+            )
+            .build();
+        let features = robot.world.get::<Features>(sensor).unwrap().0.clone();
+
+        let meta = robot.world.get::<Metadata>(features[0]).unwrap();
+        assert_eq!(meta.name, "Humidity");
+        assert_eq!(
+            robot.world.get::<Reading>(features[0]).unwrap().unit(),
+            Unit::RelativeHumidity
+        );
+        assert_eq!(
+            robot.world.get::<OutputType>(features[1]),
+            Some(&OutputType::TEMPERATURE)
+        );
+
+        // The range follows the output's unit
+        let mut reading = robot.world.get_mut::<Reading>(features[1]).unwrap();
+        assert_eq!(reading.range(), Some((0.0, f64::INFINITY)));
+        reading.set(-1.0);
+        assert_eq!(reading.status(), Status::Error(Error::OutOfRange));
+        reading.set(293.15);
+        assert!(reading.is_valid());
+
+        // The reading converts from and into its own unit
+        assert_eq!(reading.unit(), Unit::Kelvin);
+        reading.set_as(100.0, Unit::Celsius).unwrap();
+        assert!((reading.value() - 373.15).abs() < 1e-9);
+        assert!((reading.get_as(Unit::Celsius).unwrap() - 100.0).abs() < 1e-9);
+        assert_eq!(reading.set_as(1.0, Unit::Meter), None);
+    }
+
+    #[test]
+    fn outputs_are_found_by_type() {
+        use bevy_ecs::system::SystemState;
+
+        let mut robot = Robot::new();
+        let imu = SensorBuilder::new("IMU", &mut robot)
+            .with_output(OutputType::ACCELERATION)
+            .with_output(OutputType::ACCELERATION)
+            .with_output(OutputType::TEMPERATURE)
+            .build();
+        let thermometer = SensorBuilder::new("Thermometer", &mut robot)
+            .with_output(OutputType::TEMPERATURE)
+            .build();
+        let features = robot.world.get::<Features>(imu).unwrap().0.clone();
+        robot
+            .world
+            .get_mut::<Reading>(features[2])
+            .unwrap()
+            .set(30.0);
+
+        let mut state = SystemState::<SensorOutputs>::new(&mut robot.world);
+        let outputs = state.get_mut(&mut robot.world);
+
+        let reading = outputs.get(imu, OutputType::TEMPERATURE).unwrap();
+        assert_eq!((reading.value(), reading.unit()), (30.0, Unit::Celsius));
+        assert_eq!(
+            outputs
+                .outputs(imu, OutputType::ACCELERATION)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            features[0..2]
+        );
+        assert_eq!(
+            outputs.output(thermometer, OutputType::HUMIDITY),
+            Err(Error::MissingOutput(OutputType::HUMIDITY))
+        );
+        assert_eq!(
+            outputs.output(features[0], OutputType::HUMIDITY),
+            Err(Error::NotASensor)
+        );
+
+        let mut sensors: Vec<Entity> = outputs
+            .iter(OutputType::TEMPERATURE)
+            .map(|(sensor, _)| sensor)
+            .collect();
+        sensors.sort();
+        let mut expected = vec![imu, thermometer];
+        expected.sort();
+        assert_eq!(sensors, expected);
+    }
+
+    #[test]
+    fn readings_are_reachable_from_the_robot() {
+        let mut robot = Robot::new();
+        let probe = SensorBuilder::new("Probe", &mut robot)
+            .with_output(OutputType::TEMPERATURE)
+            .with_output(OutputType::HUMIDITY)
+            .build();
+        let humidity = robot.world.get::<Features>(probe).unwrap()[1];
+
+        robot.set_reading(humidity, 40.0).unwrap();
+        assert_eq!(robot.reading(humidity).unwrap().value(), 40.0);
+        assert_eq!(robot.set_reading(probe, 1.0), Err(Error::NotAnOutput));
+
+        let readings = robot.readings_of(probe).unwrap();
+        assert_eq!(readings.len(), 2);
+        assert_eq!((readings[1].0, readings[1].1.value()), (humidity, 40.0));
+        assert_eq!(readings[0].1.unit(), Unit::Celsius);
+        assert!(robot.readings_of(humidity).is_err());
     }
 }
@@ -1,7 +1,15 @@
-use crate::modules::timer::{timer_system, Duration, Timer};
-use crate::modules::{output::OutputBuilder, Descriptor, Metadata, UndefinedType};
-use crate::Robot;
+use crate::error::Error;
+use crate::hal::{Buses, I2cDevice};
+use crate::modules::handle::SensorHandle;
+use crate::modules::lifecycle::{SensorAdded, SensorRemoved};
+use crate::modules::names::SensorNames;
+use crate::modules::output::{Output, OutputBuilder, OutputType};
+use crate::modules::timer::{timed_criteria, timer_system, Duration, Timer, UsesSystem};
+use crate::modules::{Descriptor, Descriptors, Metadata, UndefinedType};
+use crate::{Robot, RobotStage};
 use bevy_ecs::prelude::*;
+use std::any::TypeId;
+use std::fmt;
 use std::ops::Deref;
 
 /// Sensor setup helper
@@ -11,8 +19,11 @@ pub struct SensorBuilder<'c> {
     robot: &'c mut Robot,
     name: String,
     metadata: Metadata,
+    // Type that provided the metadata, its id gets claimed on build
+    descriptor: Option<(TypeId, &'static str)>,
     outputs: Vec<OutputBuilder>,
-    timer: Option<Duration>,
+    timer: Option<Timer>,
+    devices: Vec<I2cDevice>,
     entity: Entity,
 }
 
@@ -25,8 +36,10 @@ impl<'c> SensorBuilder<'c> {
             name: name.into(),
             robot,
             metadata: UndefinedType.metadata(),
+            descriptor: None,
             outputs: vec![],
             timer: None,
+            devices: vec![],
             entity,
         }
     }
@@ -39,43 +52,102 @@ impl<'c> SensorBuilder<'c> {
 
     pub fn set_type<T: Descriptor + 'static>(&mut self, sensor_type: &T) {
         self.metadata = sensor_type.metadata();
+        self.descriptor = Some((TypeId::of::<T>(), std::any::type_name::<T>()));
     }
 
     /// Registers a new output
-    pub fn with_output<T: Descriptor + 'static>(mut self, output: T) -> Self {
+    pub fn with_output(mut self, output: OutputType) -> Self {
         self.set_output(output);
         self
     }
 
-    pub fn set_output<T: Descriptor + 'static>(&mut self, output: T) {
+    pub fn set_output(&mut self, output: OutputType) {
         self.outputs.push(OutputBuilder::new().with_type(output));
     }
 
+    /// Registers a preconfigured output, used for custom units or max ages
+    pub fn with_custom_output(mut self, output: OutputBuilder) -> Self {
+        self.set_custom_output(output);
+        self
+    }
+
+    pub fn set_custom_output(&mut self, output: OutputBuilder) {
+        self.outputs.push(output);
+    }
+
     /// Registers and adds a new timer
     pub fn with_timer(mut self, duration: Option<Duration>) -> Self {
-        self.timer = duration;
-        self.with_system(timer_system)
+        self.set_timer(duration);
+        self
+    }
+
+    pub fn set_timer(&mut self, duration: Option<Duration>) {
+        self.timer = duration.map(Timer::new);
+        self.robot
+            .add_system_to_stage(RobotStage::Input, timer_system);
+    }
+
+    /// Registers a preconfigured timer, used for one shot or offset timers
+    pub fn with_custom_timer(mut self, timer: Timer) -> Self {
+        self.set_custom_timer(timer);
+        self
+    }
+
+    pub fn set_custom_timer(&mut self, timer: Timer) {
+        self.timer = Some(timer);
+        self.robot
+            .add_system_to_stage(RobotStage::Input, timer_system);
     }
 
-    pub fn set_timer(mut self, duration: Option<Duration>) {
-        self.timer = duration;
-        self.set_system(timer_system);
+    /// Registers a system in the Read stage if its not already present,
+    /// a system shared by many sensors of the same module is only installed once
+    pub fn with_system<F, Params>(mut self, system: F) -> Self
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.set_system(system);
+        self
+    }
+
+    pub fn set_system<F, Params>(&mut self, system: F)
+    where
+        F: IntoSystemDescriptor<Params> + 'static,
+    {
+        self.set_system_in(RobotStage::Read, system);
     }
 
-    /// Registers a system if its not already present
-    pub fn with_system<F, Params>(self, system: F) -> Self
+    /// Registers a system in the given stage if its not already present,
+    /// the system is skipped on runs where none of the sensors that registered it is ready.
+    /// If it's shared with untimed sensors, use the Timed filter in its query to skip
+    /// the timed sensors that aren't ready.
+    /// A SystemDescriptor is never deduplicated, it gets installed on every call
+    pub fn with_system_in<F, Params>(mut self, stage: RobotStage, system: F) -> Self
     where
-        F: IntoSystemDescriptor<Params>,
+        F: IntoSystemDescriptor<Params> + 'static,
     {
-        self.robot.add_system(system);
+        self.set_system_in(stage, system);
         self
     }
 
-    pub fn set_system<F, Params>(self, system: F)
+    pub fn set_system_in<F, Params>(&mut self, stage: RobotStage, system: F)
     where
-        F: IntoSystemDescriptor<Params>,
+        F: IntoSystemDescriptor<Params> + 'static,
     {
-        self.robot.add_system(system);
+        self.add_component(UsesSystem::<F>::default());
+        // Always wrapped, whether this sensor has a timer or not, so every sensor
+        // sharing the system agrees on the form that gets installed
+        self.robot
+            .add_system_as::<F, _, _>(stage, system.with_run_criteria(timed_criteria::<F>));
+    }
+
+    /// Claims the device's I2C address for this sensor when built
+    pub fn with_device(mut self, device: &I2cDevice) -> Self {
+        self.set_device(device);
+        self
+    }
+
+    pub fn set_device(&mut self, device: &I2cDevice) {
+        self.devices.push(device.clone());
     }
 
     pub fn add_component<T: Component>(&mut self, component: T) {
@@ -91,8 +163,49 @@ impl<'c> SensorBuilder<'c> {
         self
     }
 
-    /// Handles setting up all the necessary sensor components
+    /// Handles setting up all the necessary sensor components, panics if its name is taken,
+    /// its type's id is used by another type or one of its devices collides with another
+    /// sensor's address
     pub fn build(self) -> Entity {
+        self.spawn().0
+    }
+
+    /// Same as build but returns a handle typed by the component the sensor is built around,
+    /// panics if no T was added with with_component
+    pub fn build_handle<T: Component>(self) -> SensorHandle<T> {
+        let name = self.name.clone();
+        let (entity, robot) = self.spawn();
+        robot
+            .sensor_handle(entity)
+            .unwrap_or_else(|e| panic!("sensor {}: {}", name, e))
+    }
+
+    /// Same as build but returns an error instead of panicking
+    pub fn try_build(self) -> Result<Entity, Error> {
+        self.try_spawn().map(|(entity, _)| entity)
+    }
+
+    fn spawn(self) -> (Entity, &'c mut Robot) {
+        let name = self.name.clone();
+        let id = self.metadata.id.clone();
+        self.try_spawn()
+            .unwrap_or_else(|e| panic!("failed to build sensor {} ({}): {}", name, id, e))
+    }
+
+    fn try_spawn(mut self) -> Result<(Entity, &'c mut Robot), Error> {
+        let claimed = self
+            .claim_name()
+            .and_then(|_| self.claim_id())
+            .and_then(|_| self.claim_devices());
+        if let Err(e) = claimed {
+            self.robot
+                .world
+                .resource_mut::<SensorNames>()
+                .release(self.entity);
+            self.robot.world.despawn(self.entity);
+            return Err(e);
+        }
+
         // Encapsulating this helps us borrow world multiple times
         {
             let mut ent = self.robot.world.get_entity_mut(self.entity).unwrap();
@@ -100,7 +213,7 @@ impl<'c> SensorBuilder<'c> {
             ent.insert((Name(self.name), self.metadata));
 
             if let Some(timer) = self.timer {
-                ent.insert(Timer::new(timer));
+                ent.insert(timer);
             }
         }
 
@@ -115,7 +228,41 @@ impl<'c> SensorBuilder<'c> {
             .unwrap()
             .insert(Features(feature_entities));
 
-        self.entity
+        self.robot.world.send_event(SensorAdded(self.entity));
+        Ok((self.entity, self.robot))
+    }
+
+    fn claim_name(&mut self) -> Result<(), Error> {
+        self.robot
+            .world
+            .resource_mut::<SensorNames>()
+            .claim(&self.name, self.entity)
+    }
+
+    fn claim_id(&mut self) -> Result<(), Error> {
+        let Some((type_id, type_name)) = self.descriptor else {
+            return Ok(());
+        };
+
+        self.robot
+            .world
+            .get_resource_or_insert_with(Descriptors::default)
+            .claim(self.metadata.id.clone(), type_id, type_name)
+    }
+
+    fn claim_devices(&mut self) -> Result<(), Error> {
+        if self.devices.is_empty() {
+            return Ok(());
+        }
+
+        let mut buses = self.robot.world.get_resource_or_insert_with(Buses::default);
+        for device in self.devices.iter() {
+            if let Err(e) = buses.claims.claim(device, self.entity) {
+                buses.claims.release(self.entity);
+                return Err(e);
+            }
+        }
+        Ok(())
     }
 }
 
@@ -131,8 +278,9 @@ impl Deref for Name {
 }
 
 #[derive(Component, Default, Debug)]
-/// Sensor's associated features
-pub struct Features(pub Vec<Entity>);
+/// Sensor's associated features, these are its children
+/// and each of them points back at it through its Output
+pub struct Features(pub(crate) Vec<Entity>);
 impl Deref for Features {
     type Target = Vec<Entity>;
 
@@ -147,3 +295,142 @@ impl Features {
         !self.is_empty()
     }
 }
+
+/// Broken link between a sensor and its outputs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HierarchyError {
+    /// The sensor lists an output that no longer exists
+    MissingOutput { sensor: Entity, output: Entity },
+    /// The sensor lists an output that belongs to another sensor
+    WrongSensor { sensor: Entity, output: Entity },
+    /// The output's sensor doesn't exist or doesn't list it
+    Orphan { output: Entity },
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HierarchyError::MissingOutput { sensor, output } => {
+                write!(f, "sensor {:?} lists missing output {:?}", sensor, output)
+            }
+            HierarchyError::WrongSensor { sensor, output } => write!(
+                f,
+                "sensor {:?} lists output {:?} which belongs to another sensor",
+                sensor, output
+            ),
+            HierarchyError::Orphan { output } => {
+                write!(f, "output {:?} isn't listed by its sensor", output)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+impl Robot {
+    /// Despawns a sensor with all of its components and outputs,
+    /// the I2C addresses it claimed become free again
+    pub fn remove_sensor(&mut self, sensor: Entity) -> Result<(), Error> {
+        let outputs = self
+            .world
+            .get::<Features>(sensor)
+            .ok_or(Error::NotASensor)?
+            .0
+            .clone();
+
+        for output in outputs {
+            self.world.despawn(output);
+        }
+        if let Some(mut buses) = self.world.get_resource_mut::<Buses>() {
+            buses.claims.release(sensor);
+        }
+        self.world.resource_mut::<SensorNames>().release(sensor);
+        self.world.despawn(sensor);
+        self.world.send_event(SensorRemoved(sensor));
+        Ok(())
+    }
+
+    /// Makes sure every sensor's outputs exist and point back at it,
+    /// and that every output is listed by its sensor.
+    /// Nothing calls this on its own, tests and tools call it after changing the world by hand
+    pub fn check_hierarchy(&mut self) -> Result<(), HierarchyError> {
+        let mut sensors = self.world.query::<(Entity, &Features)>();
+        let mut outputs = self.world.query::<(Entity, &Output)>();
+
+        for (sensor, features) in sensors.iter(&self.world) {
+            for output in features.iter().copied() {
+                match outputs.get(&self.world, output) {
+                    Ok((_, parent)) if **parent == sensor => {}
+                    Ok(_) => return Err(HierarchyError::WrongSensor { sensor, output }),
+                    Err(_) => return Err(HierarchyError::MissingOutput { sensor, output }),
+                }
+            }
+        }
+
+        for (output, parent) in outputs.iter(&self.world) {
+            match sensors.get(&self.world, **parent) {
+                Ok((_, features)) if features.contains(&output) => {}
+                _ => return Err(HierarchyError::Orphan { output }),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::{mock::MockI2c, I2cHandle};
+
+    #[test]
+    fn removing_a_sensor_removes_its_outputs() {
+        let mut robot = Robot::new().with_bus("i2c-1", I2cHandle::new(MockI2c::new()));
+        let device = robot.i2c_device("i2c-1", 0x44).unwrap();
+        let sensor = SensorBuilder::new("Sensor", &mut robot)
+            .with_device(&device)
+            .with_output(OutputType::TEMPERATURE)
+            .with_output(OutputType::HUMIDITY)
+            .build();
+        let other = SensorBuilder::new("Other", &mut robot)
+            .with_output(OutputType::PRESSURE)
+            .build();
+        let outputs = robot.world.get::<Features>(sensor).unwrap().0.clone();
+        assert_eq!(robot.check_hierarchy(), Ok(()));
+
+        robot.remove_sensor(sensor).unwrap();
+        assert!(robot.world.get_entity(sensor).is_none());
+        assert!(outputs.iter().all(|o| robot.world.get_entity(*o).is_none()));
+        assert_eq!(robot.i2c_owner("i2c-1", 0x44), None);
+        assert!(robot.world.get_entity(other).is_some());
+        assert_eq!(robot.check_hierarchy(), Ok(()));
+
+        assert_eq!(robot.remove_sensor(sensor), Err(Error::NotASensor));
+        // The address can be claimed again
+        SensorBuilder::new("Replacement", &mut robot)
+            .with_device(&device)
+            .build();
+        robot.run();
+    }
+
+    #[test]
+    fn broken_links_are_detected() {
+        let mut robot = Robot::new();
+        let sensor = SensorBuilder::new("Sensor", &mut robot)
+            .with_output(OutputType::TEMPERATURE)
+            .build();
+        let output = robot.world.get::<Features>(sensor).unwrap()[0];
+
+        robot.world.despawn(output);
+        assert_eq!(
+            robot.check_hierarchy(),
+            Err(HierarchyError::MissingOutput { sensor, output })
+        );
+
+        robot.world.get_mut::<Features>(sensor).unwrap().0.clear();
+        let orphan = robot.world.spawn(Output(sensor)).id();
+        assert_eq!(
+            robot.check_hierarchy(),
+            Err(HierarchyError::Orphan { output: orphan })
+        );
+    }
+}
@@ -1,50 +1,108 @@
+use crate::modules::clock::Time;
 use bevy_ecs::prelude::*;
-use std::time::SystemTime;
+use bevy_ecs::schedule::ShouldRun;
+use std::marker::PhantomData;
 
 #[derive(Component, Debug)]
 pub struct Timer {
     duration: Duration,
+    mode: TimerMode,
+    offset: std::time::Duration,
     state: State,
 }
 
 impl Timer {
+    /// Creates a repeating timer
     pub fn new(duration: Duration) -> Self {
         Self {
             duration,
+            mode: TimerMode::Repeating,
+            offset: std::time::Duration::ZERO,
             state: State::Wait,
         }
     }
 
+    /// Creates a timer that is only ready once
+    pub fn once(duration: Duration) -> Self {
+        Self::new(duration).with_mode(TimerMode::OneShot)
+    }
+
+    pub fn with_mode(mut self, mode: TimerMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Delays the first deadline, useful for spreading timers
+    /// with the same duration across different runs
+    pub fn with_offset(mut self, offset: Duration) -> Self {
+        self.offset = offset.into();
+        self
+    }
+
+    pub fn mode(&self) -> TimerMode {
+        self.mode
+    }
+
     pub fn is_ready(&self) -> bool {
-        self.state == State::Ready
+        matches!(self.state, State::Ready { .. })
     }
 
-    pub fn update(&mut self) {
+    /// One shot timers are finished once their ready state was consumed
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Finished
+    }
+
+    /// Moves the timer forward using the robot's current time
+    pub fn update(&mut self, now: std::time::Duration) {
+        let period: std::time::Duration = self.duration.into();
         match self.state {
             State::Wait => {
                 self.state = State::Waiting {
-                    time: SystemTime::now(),
+                    deadline: now + self.offset + period,
                 }
             }
-            State::Waiting { time } => {
-                let elapsed = SystemTime::now().duration_since(time).unwrap();
-                if match self.duration {
-                    Duration::Millis(duration) => elapsed.as_millis() >= duration,
-                    Duration::Micros(duration) => elapsed.as_micros() >= duration,
-                    Duration::Nanos(duration) => elapsed.as_nanos() >= duration,
-                    Duration::Secs(duration) => elapsed.as_secs() >= duration,
-                } {
-                    self.state = State::Ready;
+            State::Waiting { deadline } => {
+                if now >= deadline {
+                    // Skip the periods that were missed entirely so a late read
+                    // doesn't make the timer fire on every run to catch up
+                    let behind = (now - deadline).as_nanos();
+                    let skipped = match period.as_nanos() {
+                        0 => 0,
+                        p => behind - behind % p,
+                    };
+                    self.state = State::Ready {
+                        deadline: deadline + std::time::Duration::from_nanos(skipped as u64),
+                    };
                 }
             }
-            State::Ready => {
-                self.state = State::Wait;
-            }
+            State::Ready { .. } => self.reset(),
+            State::Finished => {}
         }
     }
+
+    /// Consumes the ready state, repeating timers wait for the next period
+    /// counted from the last deadline so they don't drift
+    pub fn reset(&mut self) {
+        if let State::Ready { deadline } = self.state {
+            self.state = match self.mode {
+                TimerMode::Repeating => State::Waiting {
+                    deadline: deadline + self.duration.into(),
+                },
+                TimerMode::OneShot => State::Finished,
+            };
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimerMode {
+    /// Ready every period, based on the first deadline
+    Repeating,
+    /// Ready once and then finished
+    OneShot,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Duration {
     Millis(u128),
     Micros(u128),
@@ -52,31 +110,272 @@ pub enum Duration {
     Secs(u64),
 }
 
+impl From<Duration> for std::time::Duration {
+    fn from(duration: Duration) -> Self {
+        let clamp = |n: u128| u64::try_from(n).unwrap_or(u64::MAX);
+        match duration {
+            Duration::Millis(n) => std::time::Duration::from_millis(clamp(n)),
+            Duration::Micros(n) => std::time::Duration::from_micros(clamp(n)),
+            Duration::Nanos(n) => std::time::Duration::from_nanos(clamp(n)),
+            Duration::Secs(n) => std::time::Duration::from_secs(n),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub enum State {
-    Wait,                         // Ask for the timer to start waiting
-    Waiting { time: SystemTime }, // Will check when time is ready
-    Ready,                        // Notifies that the timer is ready
+    Wait,                                      // Ask for the timer to start waiting
+    Waiting { deadline: std::time::Duration }, // Will check when time is ready
+    Ready { deadline: std::time::Duration },   // Notifies that the timer is ready
+    Finished,                                  // One shot timer that was already used
 }
 
-pub(crate) fn timer_system(mut query: Query<&mut Timer>) {
-    for mut timer in &mut query {
-        if !timer.is_ready() {
-            timer.update();
+#[derive(Component, Default, Debug)]
+/// Marks a timed entity whose timer is ready for the current run
+pub struct TimerReady;
+
+/// Query filter that only matches entities without a timer or whose timer is ready,
+/// add it to a sensor's query to skip the entities that shouldn't be read yet
+pub type Timed = Or<(Without<Timer>, With<TimerReady>)>;
+
+/// Timers are only ready for the run they became ready in,
+/// the ready state gets consumed at the start of the next run
+pub(crate) fn timer_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Timer, Option<&TimerReady>)>,
+) {
+    for (entity, mut timer, marker) in &mut query {
+        timer.reset();
+        timer.update(time.now());
+
+        match (timer.is_ready(), marker.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(TimerReady);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<TimerReady>();
+            }
+            _ => {}
         }
     }
 }
 
-/// Automatically checks for a timer variable and skips the current loop if true
+/// Marks a sensor that registered the system F, so F only waits on the timers of its own sensors
+#[derive(Component)]
+pub struct UsesSystem<F: 'static>(PhantomData<fn() -> F>);
+
+impl<F> Default for UsesSystem<F> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// Skips the system F on runs where none of the sensors that registered it is ready,
+/// a system only used by timed sensors needs no Timed filter of its own.
+/// When untimed sensors share F it runs every time, so the Timed filter then keeps
+/// its query to the sensors that are ready
+pub(crate) fn timed_criteria<F: 'static>(
+    query: Query<(), (With<UsesSystem<F>>, Timed)>,
+) -> ShouldRun {
+    if query.is_empty() {
+        ShouldRun::No
+    } else {
+        ShouldRun::Yes
+    }
+}
+
+/// Automatically checks for a timer variable and skips the current loop if its not ready,
+/// consuming the ready state according to the timer's mode
 #[macro_export]
 macro_rules! check_timer {
     ($timer:ident) => {
         if let Some(mut timer) = $timer {
             if timer.is_ready() {
-                timer.update();
+                timer.reset();
             } else {
                 continue;
             }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::clock::MockClock;
+    use crate::prelude::*;
+
+    #[test]
+    fn timer_follows_clock() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new().with_clock(clock.clone());
+
+        let sensor = SensorBuilder::new("Timed", &mut robot)
+            .with_timer(Some(Duration::Millis(10)))
+            .build();
+        let is_ready = |robot: &Robot| robot.world.get::<Timer>(sensor).unwrap().is_ready();
+
+        robot.run();
+        clock.advance(std::time::Duration::from_millis(9));
+        robot.run();
+        assert!(!is_ready(&robot));
+
+        clock.advance(std::time::Duration::from_millis(1));
+        robot.run();
+        assert!(is_ready(&robot));
+    }
+
+    #[derive(Component, Default)]
+    struct Reads(u32);
+
+    fn timed_reading(mut query: Query<&mut Reads, Timed>) {
+        for mut reads in &mut query {
+            reads.0 += 1;
+        }
+    }
+
+    #[test]
+    fn timer_gates_sensor_systems() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new().with_clock(clock.clone());
+
+        let fast = SensorBuilder::new("Fast", &mut robot)
+            .with_timer(Some(Duration::Millis(1)))
+            .with_system(timed_reading)
+            .with_component(Reads::default())
+            .build();
+        let slow = SensorBuilder::new("Slow", &mut robot)
+            .with_timer(Some(Duration::Millis(5)))
+            .with_component(Reads::default())
+            .build();
+
+        for _ in 0..10 {
+            clock.advance(MS);
+            robot.run();
+        }
+
+        assert_eq!(robot.world.get::<Reads>(fast).unwrap().0, 9);
+        assert_eq!(robot.world.get::<Reads>(slow).unwrap().0, 1);
+    }
+
+    #[test]
+    fn timed_and_untimed_sensors_share_a_system() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new().with_clock(clock.clone());
+
+        // The system is added before the timer, then again by a sensor without one
+        let timed = SensorBuilder::new("Timed", &mut robot)
+            .with_system(timed_reading)
+            .with_timer(Some(Duration::Millis(5)))
+            .with_component(Reads::default())
+            .build();
+        let untimed = SensorBuilder::new("Untimed", &mut robot)
+            .with_system(timed_reading)
+            .with_component(Reads::default())
+            .build();
+
+        for _ in 0..10 {
+            clock.advance(MS);
+            robot.run();
+        }
+
+        assert_eq!(robot.world.get::<Reads>(timed).unwrap().0, 1);
+        assert_eq!(robot.world.get::<Reads>(untimed).unwrap().0, 10);
+    }
+
+    fn counting(mut query: Query<&mut Reads>) {
+        for mut reads in &mut query {
+            reads.0 += 1;
+        }
+    }
+
+    #[derive(Component, Default)]
+    struct Polls(u32);
+
+    fn polling(mut query: Query<&mut Polls>) {
+        for mut polls in &mut query {
+            polls.0 += 1;
+        }
+    }
+
+    #[test]
+    fn systems_wait_on_their_own_sensors() {
+        let clock = MockClock::new();
+        let mut robot = Robot::new().with_clock(clock.clone());
+
+        // No Timed filter in counting, its criteria alone keeps it to the timer
+        let timed = SensorBuilder::new("Timed", &mut robot)
+            .with_timer(Some(Duration::Millis(5)))
+            .with_system(counting)
+            .with_component(Reads::default())
+            .build();
+        // An untimed sensor of another module doesn't make counting run
+        let untimed = SensorBuilder::new("Untimed", &mut robot)
+            .with_system(polling)
+            .with_component(Polls::default())
+            .build();
+
+        for _ in 0..10 {
+            clock.advance(MS);
+            robot.run();
+        }
+
+        assert_eq!(robot.world.get::<Reads>(timed).unwrap().0, 1);
+        assert_eq!(robot.world.get::<Polls>(untimed).unwrap().0, 10);
+    }
+
+    const MS: std::time::Duration = std::time::Duration::from_millis(1);
+
+    #[test]
+    fn repeating_timer_does_not_drift() {
+        let mut timer = Timer::new(Duration::Millis(10));
+        timer.update(std::time::Duration::ZERO);
+
+        // Read 3ms late, the next deadline should still be at 20ms
+        timer.update(MS * 13);
+        assert!(timer.is_ready());
+        timer.reset();
+
+        timer.update(MS * 19);
+        assert!(!timer.is_ready());
+        timer.update(MS * 20);
+        assert!(timer.is_ready());
+    }
+
+    #[test]
+    fn repeating_timer_skips_missed_periods() {
+        let mut timer = Timer::new(Duration::Millis(10));
+        timer.update(std::time::Duration::ZERO);
+        timer.update(MS * 35);
+        timer.reset();
+
+        timer.update(MS * 36);
+        assert!(!timer.is_ready());
+        timer.update(MS * 40);
+        assert!(timer.is_ready());
+    }
+
+    #[test]
+    fn one_shot_timer_finishes() {
+        let mut timer = Timer::once(Duration::Millis(10));
+        timer.update(std::time::Duration::ZERO);
+        timer.update(MS * 10);
+        assert!(timer.is_ready());
+        timer.reset();
+
+        timer.update(MS * 100);
+        assert!(timer.is_finished());
+        assert!(!timer.is_ready());
+    }
+
+    #[test]
+    fn offset_delays_first_deadline() {
+        let mut timer = Timer::new(Duration::Millis(10)).with_offset(Duration::Millis(5));
+        timer.update(std::time::Duration::ZERO);
+        timer.update(MS * 10);
+        assert!(!timer.is_ready());
+        timer.update(MS * 15);
+        assert!(timer.is_ready());
+    }
+}
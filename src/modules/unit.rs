@@ -0,0 +1,227 @@
+use std::fmt;
+
+/// Physical quantity measured by a unit, only units of the same quantity can be converted
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Quantity {
+    Temperature,
+    Humidity,
+    Ratio,
+    Pressure,
+    Length,
+    Voltage,
+    Current,
+    Power,
+    Acceleration,
+    AngularVelocity,
+    Illuminance,
+    Concentration,
+    Speed,
+    Angle,
+    MagneticField,
+    Mass,
+    Force,
+    Frequency,
+    SoundLevel,
+    Dimensionless,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+/// Unit of an output's reading
+pub enum Unit {
+    #[default]
+    Unitless,
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    RelativeHumidity,
+    Percent,
+    Pascal,
+    Hectopascal,
+    Kilopascal,
+    Psi,
+    Bar,
+    Meter,
+    Centimeter,
+    Millimeter,
+    Volt,
+    Millivolt,
+    Ampere,
+    Milliampere,
+    Watt,
+    Milliwatt,
+    MetersPerSecondSquared,
+    StandardGravity,
+    DegreesPerSecond,
+    RadiansPerSecond,
+    Lux,
+    PartsPerMillion,
+    PartsPerBillion,
+    MetersPerSecond,
+    KilometersPerHour,
+    Degree,
+    Radian,
+    Microtesla,
+    Gauss,
+    Kilogram,
+    Gram,
+    Newton,
+    Hertz,
+    Decibel,
+}
+
+impl Unit {
+    pub fn quantity(&self) -> Quantity {
+        match self {
+            Unit::Unitless => Quantity::Dimensionless,
+            Unit::Celsius | Unit::Fahrenheit | Unit::Kelvin => Quantity::Temperature,
+            Unit::RelativeHumidity => Quantity::Humidity,
+            Unit::Percent => Quantity::Ratio,
+            Unit::Pascal | Unit::Hectopascal | Unit::Kilopascal | Unit::Psi | Unit::Bar => {
+                Quantity::Pressure
+            }
+            Unit::Meter | Unit::Centimeter | Unit::Millimeter => Quantity::Length,
+            Unit::Volt | Unit::Millivolt => Quantity::Voltage,
+            Unit::Ampere | Unit::Milliampere => Quantity::Current,
+            Unit::Watt | Unit::Milliwatt => Quantity::Power,
+            Unit::MetersPerSecondSquared | Unit::StandardGravity => Quantity::Acceleration,
+            Unit::DegreesPerSecond | Unit::RadiansPerSecond => Quantity::AngularVelocity,
+            Unit::Lux => Quantity::Illuminance,
+            Unit::PartsPerMillion | Unit::PartsPerBillion => Quantity::Concentration,
+            Unit::MetersPerSecond | Unit::KilometersPerHour => Quantity::Speed,
+            Unit::Degree | Unit::Radian => Quantity::Angle,
+            Unit::Microtesla | Unit::Gauss => Quantity::MagneticField,
+            Unit::Kilogram | Unit::Gram => Quantity::Mass,
+            Unit::Newton => Quantity::Force,
+            Unit::Hertz => Quantity::Frequency,
+            Unit::Decibel => Quantity::SoundLevel,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Unitless => "",
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Kelvin => "K",
+            Unit::RelativeHumidity => "%RH",
+            Unit::Percent => "%",
+            Unit::Pascal => "Pa",
+            Unit::Hectopascal => "hPa",
+            Unit::Kilopascal => "kPa",
+            Unit::Psi => "psi",
+            Unit::Bar => "bar",
+            Unit::Meter => "m",
+            Unit::Centimeter => "cm",
+            Unit::Millimeter => "mm",
+            Unit::Volt => "V",
+            Unit::Millivolt => "mV",
+            Unit::Ampere => "A",
+            Unit::Milliampere => "mA",
+            Unit::Watt => "W",
+            Unit::Milliwatt => "mW",
+            Unit::MetersPerSecondSquared => "m/s²",
+            Unit::StandardGravity => "gₙ",
+            Unit::DegreesPerSecond => "°/s",
+            Unit::RadiansPerSecond => "rad/s",
+            Unit::Lux => "lx",
+            Unit::PartsPerMillion => "ppm",
+            Unit::PartsPerBillion => "ppb",
+            Unit::MetersPerSecond => "m/s",
+            Unit::KilometersPerHour => "km/h",
+            Unit::Degree => "°",
+            Unit::Radian => "rad",
+            Unit::Microtesla => "µT",
+            Unit::Gauss => "G",
+            Unit::Kilogram => "kg",
+            Unit::Gram => "g",
+            Unit::Newton => "N",
+            Unit::Hertz => "Hz",
+            Unit::Decibel => "dB",
+        }
+    }
+
+    /// Scale and offset used to go into the quantity's base unit
+    /// base = value * scale + offset
+    fn to_base(self) -> (f64, f64) {
+        match self {
+            Unit::Celsius => (1.0, 273.15),
+            Unit::Fahrenheit => (5.0 / 9.0, 459.67 * 5.0 / 9.0),
+            Unit::Hectopascal => (100.0, 0.0),
+            Unit::Kilopascal => (1_000.0, 0.0),
+            Unit::Psi => (6_894.757_293_168, 0.0),
+            Unit::Bar => (100_000.0, 0.0),
+            Unit::Centimeter => (0.01, 0.0),
+            Unit::Millimeter
+            | Unit::Millivolt
+            | Unit::Milliampere
+            | Unit::Milliwatt
+            | Unit::PartsPerBillion
+            | Unit::Gram => (0.001, 0.0),
+            Unit::StandardGravity => (9.806_65, 0.0),
+            Unit::RadiansPerSecond | Unit::Radian => (180.0 / std::f64::consts::PI, 0.0),
+            Unit::KilometersPerHour => (1.0 / 3.6, 0.0),
+            Unit::Gauss => (100.0, 0.0),
+            _ => (1.0, 0.0),
+        }
+    }
+
+    /// Converts a value from this unit into another,
+    /// returns None if both units don't measure the same quantity
+    pub fn convert(&self, value: f64, to: Unit) -> Option<f64> {
+        if self.quantity() != to.quantity() {
+            return None;
+        }
+
+        let (scale, offset) = self.to_base();
+        let base = value * scale + offset;
+        let (scale, offset) = to.to_base();
+        Some((base - offset) / scale)
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Option<f64>, b: f64) {
+        assert!((a.unwrap() - b).abs() < 1e-6, "{:?} != {}", a, b);
+    }
+
+    #[test]
+    fn converts_temperature() {
+        assert_close(Unit::Celsius.convert(100.0, Unit::Fahrenheit), 212.0);
+        assert_close(Unit::Fahrenheit.convert(32.0, Unit::Celsius), 0.0);
+        assert_close(Unit::Kelvin.convert(0.0, Unit::Celsius), -273.15);
+    }
+
+    #[test]
+    fn converts_pressure() {
+        assert_close(Unit::Hectopascal.convert(1013.25, Unit::Pascal), 101_325.0);
+        assert_close(Unit::Psi.convert(1.0, Unit::Kilopascal), 6.894_757_293_168);
+    }
+
+    #[test]
+    fn converts_scaled_units() {
+        assert_close(
+            Unit::KilometersPerHour.convert(36.0, Unit::MetersPerSecond),
+            10.0,
+        );
+        assert_close(Unit::Gauss.convert(0.5, Unit::Microtesla), 50.0);
+        assert_close(
+            Unit::Radian.convert(std::f64::consts::PI, Unit::Degree),
+            180.0,
+        );
+    }
+
+    #[test]
+    fn rejects_different_quantities() {
+        assert_eq!(Unit::Celsius.convert(1.0, Unit::Pascal), None);
+        assert_eq!(Unit::RelativeHumidity.convert(1.0, Unit::Percent), None);
+    }
+}
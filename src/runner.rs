@@ -0,0 +1,297 @@
+use crate::modules::clock::Time;
+use crate::modules::lifecycle::ShutdownReport;
+use crate::modules::timer::Duration;
+use crate::Robot;
+use bevy_ecs::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Set this resource to stop any of the Robot's run loops after the current tick
+#[derive(Resource, Default, Debug)]
+pub struct Shutdown(pub bool);
+impl Shutdown {
+    pub fn request(&mut self) {
+        self.0 = true;
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0
+    }
+}
+
+/// Shutdown request that can be shared with other threads and signal handlers
+#[derive(Resource, Clone, Default, Debug)]
+pub struct ShutdownFlag(Arc<AtomicBool>);
+impl ShutdownFlag {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// How long a run loop should last
+#[derive(Clone, Copy, Debug)]
+pub enum Limit {
+    Ticks(u64),
+    Time(Duration),
+}
+
+/// Summary of a finished run loop
+#[derive(Default, Debug)]
+pub struct LoopReport {
+    pub ticks: u64,
+    /// Amount of ticks that took longer than their period
+    pub overruns: u64,
+    /// Longest time a tick went over its period
+    pub max_overrun: std::time::Duration,
+    /// Set when the loop stopped on a shutdown request and shut the robot down
+    pub shutdown: Option<ShutdownReport>,
+}
+
+impl LoopReport {
+    /// Exit status for the process, failure if a module didn't shut down cleanly
+    pub fn exit_code(&self) -> std::process::ExitCode {
+        self.shutdown
+            .as_ref()
+            .map_or(std::process::ExitCode::SUCCESS, ShutdownReport::exit_code)
+    }
+}
+
+impl Robot {
+    /// Runs at a fixed rate until the limit is reached
+    pub fn run_for(&mut self, period: Duration, limit: Limit) -> LoopReport {
+        match limit {
+            Limit::Ticks(ticks) => self.run_loop(period, |_, report, _| report.ticks >= ticks),
+            Limit::Time(span) => {
+                let span: std::time::Duration = span.into();
+                self.run_loop(period, |_, _, elapsed| elapsed >= span)
+            }
+        }
+    }
+
+    /// Runs at a fixed rate until the predicate returns true
+    pub fn run_until<F>(&mut self, period: Duration, mut predicate: F) -> LoopReport
+    where
+        F: FnMut(&Robot) -> bool,
+    {
+        self.run_loop(period, |robot, _, _| predicate(robot))
+    }
+
+    /// Runs at a fixed rate until a Shutdown is requested, then shuts the robot down
+    pub fn run_forever(&mut self, period: Duration) -> LoopReport {
+        self.run_loop(period, |_, _, _| false)
+    }
+
+    /// Requests the running loop to stop after its current tick
+    pub fn request_shutdown(&mut self) {
+        self.world.resource_mut::<Shutdown>().request();
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        let requested = self.world.resource::<Shutdown>().is_requested();
+        let flagged = self
+            .world
+            .get_resource::<ShutdownFlag>()
+            .is_some_and(ShutdownFlag::is_requested);
+        requested || flagged
+    }
+
+    /// Flag other threads can use to request a shutdown
+    pub fn shutdown_flag(&mut self) -> ShutdownFlag {
+        self.world
+            .get_resource_or_insert_with(ShutdownFlag::default)
+            .clone()
+    }
+
+    /// Requests a shutdown on SIGINT or SIGTERM, a second signal exits right away
+    /// in case the shutdown hangs
+    #[cfg(feature = "signals")]
+    pub fn handle_signals(&mut self) -> Result<(), crate::error::Error> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+
+        let flag = self.shutdown_flag();
+        for signal in [SIGINT, SIGTERM] {
+            // Registered first so it sees the flag before the first signal sets it
+            signal_hook::flag::register_conditional_shutdown(signal, 1, flag.0.clone())?;
+            signal_hook::flag::register(signal, flag.0.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Loops stopped by a shutdown request also shut the robot down.
+    /// Ticks are timed and paced with the Time resource's clock, so a MockClock steps through them
+    fn run_loop<F>(&mut self, period: Duration, mut done: F) -> LoopReport
+    where
+        F: FnMut(&Robot, &LoopReport, std::time::Duration) -> bool,
+    {
+        let period: std::time::Duration = period.into();
+        let mut report = LoopReport::default();
+        let started = self.clock_now();
+        let mut deadline = started;
+
+        while !self.is_shutdown_requested()
+            && !done(self, &report, self.clock_now().saturating_sub(started))
+        {
+            let tick = self.clock_now();
+            self.run();
+            report.ticks += 1;
+
+            let now = self.clock_now();
+            let elapsed = now.saturating_sub(tick);
+            if elapsed > period {
+                // Resync instead of trying to catch up on the missed ticks
+                report.overruns += 1;
+                report.max_overrun = report.max_overrun.max(elapsed - period);
+                deadline = now;
+            } else {
+                deadline += period;
+                let time = self.world.resource::<Time>();
+                time.clock().sleep(deadline.saturating_sub(now));
+            }
+        }
+
+        if self.is_shutdown_requested() {
+            report.shutdown = Some(self.shutdown());
+            // The next loop starts the robot again
+            self.clear_shutdown_request();
+        }
+        report
+    }
+
+    fn clear_shutdown_request(&mut self) {
+        self.world.resource_mut::<Shutdown>().0 = false;
+        if let Some(flag) = self.world.get_resource::<ShutdownFlag>() {
+            flag.0.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn clock_now(&self) -> std::time::Duration {
+        self.world.resource::<Time>().clock().now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::modules::actuator::{ActuatorBuilder, Setpoint};
+    use crate::modules::clock::{Clock, MockClock};
+    use crate::modules::{lifecycle::Hooks, Module};
+    use crate::RobotStage;
+
+    #[derive(Resource, Default)]
+    struct Counter(u64);
+
+    fn count(mut counter: ResMut<Counter>) {
+        counter.0 += 1;
+    }
+
+    fn slow() {
+        std::thread::sleep(std::time::Duration::from_millis(3));
+    }
+
+    fn stop_at_five(counter: Res<Counter>, mut shutdown: ResMut<Shutdown>) {
+        if counter.0 >= 5 {
+            shutdown.request();
+        }
+    }
+
+    fn robot() -> Robot {
+        let mut robot = Robot::new().with_system(count);
+        robot.world.init_resource::<Counter>();
+        robot
+    }
+
+    #[test]
+    fn runs_for_ticks() {
+        let mut robot = robot();
+        let report = robot.run_for(Duration::Millis(1), Limit::Ticks(4));
+
+        assert_eq!(report.ticks, 4);
+        assert_eq!(robot.world.resource::<Counter>().0, 4);
+    }
+
+    #[test]
+    fn runs_until_predicate() {
+        let mut robot = robot();
+        let report = robot.run_until(Duration::Micros(100), |robot| {
+            robot.world.resource::<Counter>().0 == 3
+        });
+
+        assert_eq!(report.ticks, 3);
+    }
+
+    #[test]
+    fn runs_until_shutdown() {
+        let mut robot = robot().with_system_in(RobotStage::Output, stop_at_five);
+        let report = robot.run_forever(Duration::Micros(100));
+
+        assert_eq!(report.ticks, 5);
+        assert!(report.shutdown.unwrap().is_clean());
+    }
+
+    /// Module whose hardware can't be put in a safe state
+    struct Brake;
+
+    impl Module<()> for Brake {
+        fn init(self, _robot: &mut Robot) {}
+
+        fn hooks(_: &()) -> Hooks {
+            Hooks::new().on_shutdown(|_| Err(Error::Disconnected))
+        }
+    }
+
+    #[test]
+    fn flag_shuts_the_robot_down() {
+        let mut robot = robot().with(Brake);
+        let pump = ActuatorBuilder::new("Pump", &mut robot)
+            .with_setpoint(1.0)
+            .with_safe_setpoint(0.0)
+            .build();
+
+        // Stands in for a signal handler
+        let flag = robot.shutdown_flag();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            flag.request();
+        });
+        let report = robot.run_forever(Duration::Micros(100));
+
+        assert!(report.ticks > 0);
+        assert_eq!(robot.world.get::<Setpoint>(pump).unwrap().0, 0.0);
+        let shutdown = report.shutdown.unwrap();
+        assert!(!shutdown.is_clean());
+        assert_eq!(
+            shutdown.failures,
+            [(std::any::type_name::<Brake>(), Error::Disconnected)]
+        );
+
+        // The request was handled, the next loop starts the robot again
+        let report = robot.run_for(Duration::Micros(100), Limit::Ticks(3));
+        assert_eq!(report.ticks, 3);
+        assert!(report.shutdown.is_none());
+    }
+
+    #[test]
+    fn mock_clock_steps_the_loop() {
+        let clock = MockClock::new();
+        let mut robot = robot().with_clock(clock.clone());
+        let report = robot.run_for(Duration::Secs(1), Limit::Time(Duration::Secs(5)));
+
+        assert_eq!(report.ticks, 5);
+        assert_eq!(report.overruns, 0);
+        assert_eq!(clock.now(), std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reports_overruns() {
+        let mut robot = robot().with_system(slow);
+        let report = robot.run_for(Duration::Millis(1), Limit::Ticks(2));
+
+        assert_eq!(report.overruns, 2);
+        assert!(report.max_overrun >= std::time::Duration::from_millis(2));
+    }
+}
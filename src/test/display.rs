@@ -1,7 +1,11 @@
-use crate::modules::output::{OutputType, Reading};
-use crate::modules::sensor::{Features, SensorBuilder};
-use crate::modules::{Descriptor, Metadata, Module};
-use crate::Robot;
+use crate::modules::handle::SensorHandle;
+use crate::modules::output::{OutputType, SensorOutputs};
+use crate::modules::sensor::SensorBuilder;
+use crate::modules::unit::Unit;
+use crate::modules::{Descriptor, Id, Metadata, Module};
+use crate::test::moisture_sensor::MoistureSensor;
+use crate::test::temp_sensor::TemperatureSensor;
+use crate::{Robot, RobotStage};
 use bevy_ecs::prelude::*;
 
 #[derive(Component, Clone)]
@@ -11,14 +15,20 @@ pub struct DisplayComponent {
 }
 
 impl DisplayComponent {
-    pub fn new(temp: Entity, moisture: Entity) -> Self {
-        Self { temp, moisture }
+    pub fn new(
+        temp: &SensorHandle<TemperatureSensor>,
+        moisture: &SensorHandle<MoistureSensor>,
+    ) -> Self {
+        Self {
+            temp: temp.entity(),
+            moisture: moisture.entity(),
+        }
     }
 }
 
 impl Descriptor for DisplayComponent {
-    fn id(&self) -> u8 {
-        105
+    fn id(&self) -> Id {
+        Id::new("robotrs", "example", "display")
     }
 
     fn name(&self) -> String {
@@ -34,7 +44,7 @@ impl Module<Entity> for DisplayComponent {
     fn init(self, robot: &mut Robot) -> Entity {
         SensorBuilder::new("Display", robot)
             .with_type(&self)
-            .with_system(display_data)
+            .with_system_in(RobotStage::Output, display_data)
             .with_component(self.clone())
             .build()
     }
@@ -42,33 +52,34 @@ impl Module<Entity> for DisplayComponent {
 
 fn display_data(
     display_query: Query<&DisplayComponent>,
-    features: Query<(&Features, &Metadata)>,
-    reading: Query<(&Reading, &Metadata)>,
+    sensors: Query<&Metadata>,
+    outputs: SensorOutputs,
 ) {
     for display in display_query.iter() {
-        let (temp_features, temp_meta) = features.get(display.temp).unwrap();
-        let (moisture_features, moisture_meta) = features.get(display.moisture).unwrap();
-
-        let (moisture_reading, _) = reading.get(moisture_features.0[0]).unwrap();
-        let mut humidity_reading = 0.0;
-        let mut temp_reading = 0.0;
+        let (Ok(temp_meta), Ok(moisture_meta)) =
+            (sensors.get(display.temp), sensors.get(display.moisture))
+        else {
+            continue;
+        };
 
-        for feature in temp_features.0.iter() {
-            let (read, meta) = reading.get(*feature).unwrap();
-
-            if meta.id == OutputType::Temperature.id() {
-                temp_reading = read.0;
-            } else {
-                humidity_reading = read.0;
-            }
-        }
+        let moisture_reading = outputs
+            .get(display.moisture, OutputType::MOISTURE)
+            .map_or(0.0, |read| read.value());
+        let temp_reading = outputs
+            .get(display.temp, OutputType::TEMPERATURE)
+            .ok()
+            .and_then(|read| read.get_as(Unit::Fahrenheit))
+            .unwrap_or_default();
+        let humidity_reading = outputs
+            .get(display.temp, OutputType::HUMIDITY)
+            .map_or(0.0, |read| read.value());
 
         println!(
-            "{:?} {:?} {:?} read {:?} moisture",
+            "{} {:?} {:?} read {:?} moisture",
             moisture_meta.id, moisture_meta.name, moisture_meta.description, moisture_reading
         );
         println!(
-            "{:?} {:?} {:?} read {:?} temperature and {:?} humidity",
+            "{} {:?} {:?} read {:?}°F temperature and {:?} humidity",
             temp_meta.id, temp_meta.name, temp_meta.description, temp_reading, humidity_reading
         );
     }
@@ -4,6 +4,8 @@ mod temp_sensor;
 
 #[cfg(test)]
 mod tests {
+    use crate::error::Error;
+    use crate::hal::{mock::MockAdc, AdcHandle};
     use crate::test::display::DisplayComponent;
     use crate::test::moisture_sensor::MoistureComponent;
     use crate::test::temp_sensor::TemperatureComponent;
@@ -11,19 +13,36 @@ mod tests {
 
     #[test]
     fn demo_application() {
-        let mut robot = Robot::new();
+        let adc = MockAdc::new(12);
+        adc.set(100, 2048);
+        let mut robot = Robot::new().with_bus("adc", AdcHandle::new(adc.clone()));
 
-        let temperature_sensor =
+        let temperature_sensors =
             robot.add(TemperatureComponent::new().add("Temperature Sensor", 10));
-        let moisture_sensor = robot.add(MoistureComponent::new().add("Moisture Sensor", 100));
+        let moisture_sensors = robot.add(MoistureComponent::new().add("Moisture Sensor", 100));
 
         robot.add(DisplayComponent::new(
-            temperature_sensor.sensors[0].0,
-            moisture_sensor.sensors[0].0,
+            &temperature_sensors[0],
+            &moisture_sensors[0],
         ));
-        
-        for _ in 0..10 {
+
+        for _ in 0..5 {
+            robot.run();
+        }
+        assert!(robot.faults().is_empty());
+
+        // Simulate a probe returning more than the ADC's resolution
+        adc.set(100, 5000);
+        for _ in 0..5 {
             robot.run();
         }
+
+        let faults = robot.faults();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].0, moisture_sensors[0].entity());
+        assert_eq!(faults[0].1.error, Error::OutOfRange);
+
+        robot.clear_faults();
+        assert!(robot.faults().is_empty());
     }
 }
@@ -1,6 +1,11 @@
+use crate::error::{Error, Result};
+use crate::hal::{Adc, AdcHandle};
+use crate::modules::fault::Faults;
+use crate::modules::handle::SensorHandle;
 use crate::modules::output::{OutputType, Reading};
 use crate::modules::sensor::{Features, SensorBuilder};
-use crate::modules::{Descriptor, Module};
+use crate::modules::timer::Timed;
+use crate::modules::{Descriptor, Id, Module};
 use crate::Robot;
 use bevy_ecs::prelude::*;
 // Component
@@ -19,71 +24,80 @@ impl MoistureComponent {
     }
 }
 
-pub struct InitializedMoistureSensors {
-    pub sensors: Vec<(Entity, String, u8)>,
-}
-
-impl Module<InitializedMoistureSensors> for MoistureComponent {
-    fn init(self, robot: &mut Robot) -> InitializedMoistureSensors {
+impl Module<Vec<SensorHandle<MoistureSensor>>> for MoistureComponent {
+    fn init(self, robot: &mut Robot) -> Vec<SensorHandle<MoistureSensor>> {
+        let adc = robot
+            .bus::<AdcHandle>("adc")
+            .expect("moisture sensors need an adc bus");
         let mut initialized = vec![];
 
         for (s_name, port) in self.sensors.iter() {
-            let sensor_type = MoistureSensor::new(*port);
+            let sensor_type = MoistureSensor::new(adc.clone(), *port);
 
             let s = SensorBuilder::new(&format!("Moisture - {}", s_name), robot)
                 .with_type(&sensor_type)
-                .with_output(OutputType::Moisture)
+                .with_output(OutputType::MOISTURE)
                 .with_system(moisture_reading)
                 .with_component(sensor_type)
-                .build();
+                .build_handle();
 
-            initialized.push((s, s_name.clone(), *port));
+            initialized.push(s);
         }
 
-        InitializedMoistureSensors {
-            sensors: initialized,
-        }
+        initialized
     }
 }
 
 fn moisture_reading(
-    mut query: Query<(&mut MoistureSensor, &Features)>,
+    mut query: Query<(Entity, &mut MoistureSensor, &Features), Timed>,
     mut reading: Query<&mut Reading>,
+    mut faults: Faults,
 ) {
-    for (mut sensor, features) in query.iter_mut() {
+    for (entity, mut sensor, features) in query.iter_mut() {
+        let Ok(mut read) = reading.get_mut(features.0[0]) else {
+            continue;
+        };
+
         // Simulate some reading
-        let mut read = reading.get_mut(features.0[0]).unwrap();
-        read.0 = sensor.read();
+        match sensor.read() {
+            Ok(value) => {
+                read.set(value);
+                faults.clear(entity);
+            }
+            Err(error) => {
+                read.fail(error);
+                faults.report(entity, error);
+            }
+        }
     }
 }
 
 // Sensor
 #[derive(Component, Clone)]
 pub struct MoistureSensor {
-    // Assume the moisture sensor only needs the port to read
+    // Assume the moisture sensor is a probe on one of the ADC's channels
+    adc: AdcHandle,
     port: u8,
-
-    // We use this as a counter
-    last_read: f64,
 }
 
 impl MoistureSensor {
-    fn new(port: u8) -> Self {
-        Self {
-            port,
-            last_read: 0.0,
-        }
+    fn new(adc: AdcHandle, port: u8) -> Self {
+        Self { adc, port }
     }
 
-    fn read(&mut self) -> f64 {
-        self.last_read += 11.0;
-        self.last_read
+    fn read(&mut self) -> Result<f64> {
+        let raw = self.adc.read(self.port)?;
+        let max = self.adc.max_value();
+        if raw > max {
+            return Err(Error::OutOfRange);
+        }
+        Ok(raw as f64 / max as f64 * 100.0)
     }
 }
 
 impl Descriptor for MoistureSensor {
-    fn id(&self) -> u8 {
-        100
+    fn id(&self) -> Id {
+        Id::new("robotrs", "example", "moisture")
     }
 
     fn name(&self) -> String {
@@ -1,6 +1,8 @@
+use crate::modules::handle::SensorHandle;
 use crate::modules::output::{OutputType, Reading};
 use crate::modules::sensor::{Features, SensorBuilder};
-use crate::modules::{Descriptor, Metadata, Module};
+use crate::modules::unit::Unit;
+use crate::modules::{Descriptor, Id, Module};
 use crate::Robot;
 use bevy_ecs::prelude::*;
 pub struct TemperatureComponent {
@@ -18,49 +20,45 @@ impl TemperatureComponent {
     }
 }
 
-pub struct InitializedTemperatureSensors {
-    pub sensors: Vec<(Entity, String, u8)>,
-}
-
-impl Module<InitializedTemperatureSensors> for TemperatureComponent {
-    fn init(self, robot: &mut Robot) -> InitializedTemperatureSensors {
+impl Module<Vec<SensorHandle<TemperatureSensor>>> for TemperatureComponent {
+    fn init(self, robot: &mut Robot) -> Vec<SensorHandle<TemperatureSensor>> {
         let mut initialized = vec![];
 
-        for (s_name, port) in self.sensors.iter() {
-            let sensor_type = TemperatureSensor::new(*port);
+        for (s_name, _port) in self.sensors.iter() {
+            let sensor_type = TemperatureSensor::new();
 
             let s = SensorBuilder::new(&format!("Temperature - {}", s_name), robot)
                 .with_type(&sensor_type)
-                .with_output(OutputType::Temperature)
-                .with_output(OutputType::Humidity)
+                .with_output(OutputType::TEMPERATURE)
+                .with_output(OutputType::HUMIDITY)
                 .with_component(sensor_type)
                 .with_system(temperature_reading)
-                .build();
+                .build_handle();
 
-            initialized.push((s, s_name.clone(), *port));
+            initialized.push(s);
         }
 
-        InitializedTemperatureSensors {
-            sensors: initialized,
-        }
+        initialized
     }
 }
 
 fn temperature_reading(
     mut query: Query<(&mut TemperatureSensor, &Features)>,
-    mut reading: Query<(&mut Reading, &Metadata)>,
+    mut reading: Query<(&mut Reading, &OutputType)>,
 ) {
     for (mut sensor, features) in query.iter_mut() {
         let sensor_reading = sensor.read();
         for feature in features.0.iter() {
-            let (mut read, meta) = reading.get_mut(*feature).unwrap();
-
-            let mut r = sensor_reading;
-            if OutputType::Temperature.id() == meta.id {
-                r *= 2.0;
+            let Ok((mut read, kind)) = reading.get_mut(*feature) else {
+                continue;
+            };
+
+            if *kind == OutputType::TEMPERATURE {
+                // Simulate a sensor that reports in fahrenheit
+                read.set_as(sensor_reading * 2.0, Unit::Fahrenheit);
+            } else {
+                read.set(sensor_reading);
             }
-
-            read.0 = r;
         }
     }
 }
@@ -68,19 +66,13 @@ fn temperature_reading(
 // Sensor
 #[derive(Component, Clone)]
 pub struct TemperatureSensor {
-    // Assume the moisture sensor only needs the port to read
-    port: u8,
-
-    // We use this as a counter
+    // We use this as a counter, the simulated sensor doesn't need its port to read
     last_read: f64,
 }
 
 impl TemperatureSensor {
-    fn new(port: u8) -> Self {
-        Self {
-            port,
-            last_read: 0.0,
-        }
+    fn new() -> Self {
+        Self { last_read: 0.0 }
     }
 
     fn read(&mut self) -> f64 {
@@ -90,8 +82,8 @@ impl TemperatureSensor {
 }
 
 impl Descriptor for TemperatureSensor {
-    fn id(&self) -> u8 {
-        101
+    fn id(&self) -> Id {
+        Id::new("robotrs", "example", "temperature")
     }
 
     fn name(&self) -> String {